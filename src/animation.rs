@@ -0,0 +1,413 @@
+//! Encoders for rendered animations: GIF, APNG, animated WebP and Y4M video.
+//!
+//! GIF is limited to 256 colors a frame. APNG and WebP are lossless, and WebP files are usually
+//! the smallest. APNG and WebP need the number of frames or the total size up front, so those
+//! encoders keep the encoded frames in memory until `finish`.
+//!
+//! Y4M (YUV4MPEG2) is uncompressed video that is written as the frames come, for piping into
+//! a video encoder: `solids -f - | ffmpeg -i - out.mp4`. A path of `-` writes to stdout.
+//!
+//! Every frame is shown for the frame delay, except the last, which is held a while longer so the
+//! finished solid can be seen before the animation starts over. Video has a fixed frame rate, so
+//! there the last frame is repeated instead.
+
+use std::fs::File;
+use std::io::{BufWriter, Write};
+use std::path::Path;
+
+use clap::ValueEnum;
+use image::codecs::gif::{GifEncoder, Repeat};
+use image::{Delay, Frame, RgbaImage};
+use strum::Display;
+
+#[derive(Clone, Copy, Debug, Display, PartialEq, ValueEnum)]
+#[strum(serialize_all = "kebab-case")]
+pub enum AnimFormat {
+    Gif,
+    /// Animated PNG.
+    Apng,
+    /// Animated lossless WebP.
+    Webp,
+    /// Uncompressed YUV4MPEG2 video.
+    Y4m,
+}
+
+impl AnimFormat {
+    /// The format to write to `path`: `format` if given, else the one its extension names, else
+    /// Y4M for stdout and GIF for files.
+    pub fn for_path(format: Option<AnimFormat>, path: Option<&Path>) -> AnimFormat {
+        format
+            .or_else(|| {
+                if path? == Path::new("-") {
+                    return Some(AnimFormat::Y4m);
+                }
+                let ext = path?.extension()?.to_str()?.to_ascii_lowercase();
+                match ext.as_str() {
+                    "gif" => Some(AnimFormat::Gif),
+                    "png" | "apng" => Some(AnimFormat::Apng),
+                    "webp" => Some(AnimFormat::Webp),
+                    "y4m" => Some(AnimFormat::Y4m),
+                    _ => None,
+                }
+            })
+            .unwrap_or(AnimFormat::Gif)
+    }
+
+    pub fn extension(&self) -> &'static str {
+        match self {
+            AnimFormat::Gif => "gif",
+            AnimFormat::Apng => "png",
+            AnimFormat::Webp => "webp",
+            AnimFormat::Y4m => "y4m",
+        }
+    }
+}
+
+pub struct AnimParams {
+    pub format: AnimFormat,
+    /// Time each frame is shown, in milliseconds.
+    pub frame_delay_ms: u32,
+    /// Time the last frame is shown on top of the frame delay, in milliseconds.
+    pub hold_ms: u32,
+    /// Number of times the animation plays; 0 plays it forever. Video doesn't loop.
+    pub loop_count: u32,
+}
+
+/// An animation being written. Frames are passed on to the encoder one behind, as the last one
+/// is only known to be the last once the animation is finished. This is the only place the delay
+/// of each frame is decided; the encoders just write what they are given.
+pub struct Animation {
+    encoder: Box<dyn AnimationEncoder>,
+    last: Option<ndarray::Array3<u8>>,
+    frame_delay_ms: u32,
+    hold_ms: u32,
+}
+
+impl Animation {
+    /// Append an RGB image, an (h, w, 3) array.
+    pub fn add_frame(&mut self, image: &ndarray::Array3<u8>) {
+        if let Some(last) = self.last.replace(image.clone()) {
+            self.encoder.add_frame(&last, self.frame_delay_ms);
+        }
+    }
+
+    /// Write out the held last frame and whatever else is left.
+    pub fn finish(mut self) {
+        if let Some(last) = self.last.take() {
+            self.encoder
+                .add_frame(&last, self.frame_delay_ms.saturating_add(self.hold_ms));
+        }
+        self.encoder.finish();
+    }
+}
+
+trait AnimationEncoder: Send {
+    /// Append an RGB image, an (h, w, 3) array, shown for `delay_ms` milliseconds.
+    fn add_frame(&mut self, image: &ndarray::Array3<u8>, delay_ms: u32);
+    /// Write out whatever is left.
+    fn finish(self: Box<Self>);
+}
+
+type Output = BufWriter<Box<dyn Write + Send>>;
+
+/// An animation writing `params.format` to `path`, or to stdout for `-`.
+pub fn encoder(path: &Path, params: &AnimParams) -> Animation {
+    let file: Output = BufWriter::new(if path == Path::new("-") {
+        Box::new(std::io::stdout())
+    } else {
+        Box::new(File::create(path).expect("failed to create animation output file"))
+    });
+    Animation {
+        encoder: format_encoder(file, params),
+        last: None,
+        frame_delay_ms: params.frame_delay_ms,
+        hold_ms: params.hold_ms,
+    }
+}
+
+fn format_encoder(file: Output, params: &AnimParams) -> Box<dyn AnimationEncoder> {
+    match params.format {
+        AnimFormat::Gif => {
+            let mut encoder = GifEncoder::new_with_speed(file, 10);
+            // GIF counts the repeats after the first play, where no repeats is no loop extension.
+            let repeat = match params.loop_count {
+                0 => Repeat::Infinite,
+                plays => Repeat::Finite((plays - 1).min(u16::MAX as u32) as u16),
+            };
+            encoder.set_repeat(repeat).expect("couldn't set repeat");
+            Box::new(Gif { encoder })
+        }
+        AnimFormat::Apng => Box::new(Apng {
+            file,
+            frames: Vec::new(),
+            size: (0, 0),
+            loop_count: params.loop_count,
+        }),
+        AnimFormat::Webp => Box::new(Webp {
+            file,
+            frames: Vec::new(),
+            size: (0, 0),
+            loop_count: params.loop_count,
+        }),
+        AnimFormat::Y4m => Box::new(Y4m {
+            file,
+            frame_period_ms: None,
+        }),
+    }
+}
+
+fn rgb_bytes(image: &ndarray::Array3<u8>) -> Vec<u8> {
+    match image.as_standard_layout().as_slice() {
+        Some(slice) => slice.to_vec(),
+        None => image.iter().copied().collect(),
+    }
+}
+
+struct Gif {
+    encoder: GifEncoder<Output>,
+}
+
+impl AnimationEncoder for Gif {
+    fn add_frame(&mut self, image: &ndarray::Array3<u8>, delay_ms: u32) {
+        let (h, w, _) = image.dim();
+        let rgba = RgbaImage::from_fn(w as u32, h as u32, |x, y| {
+            let (x, y) = (x as usize, y as usize);
+            image::Rgba([image[(y, x, 0)], image[(y, x, 1)], image[(y, x, 2)], 255])
+        });
+        self.encoder
+            .encode_frame(Frame::from_parts(
+                rgba,
+                0,
+                0,
+                Delay::from_numer_denom_ms(delay_ms, 1),
+            ))
+            .expect("failed to encode frame");
+    }
+
+    fn finish(self: Box<Self>) {}
+}
+
+struct Apng {
+    file: Output,
+    /// Each frame and its delay.
+    frames: Vec<(Vec<u8>, u32)>,
+    size: (u32, u32),
+    loop_count: u32,
+}
+
+impl AnimationEncoder for Apng {
+    fn add_frame(&mut self, image: &ndarray::Array3<u8>, delay_ms: u32) {
+        let (h, w, _) = image.dim();
+        self.size = (w as u32, h as u32);
+        self.frames.push((rgb_bytes(image), delay_ms));
+    }
+
+    fn finish(self: Box<Self>) {
+        if self.frames.is_empty() {
+            return;
+        }
+        let mut encoder = png::Encoder::new(self.file, self.size.0, self.size.1);
+        encoder.set_color(png::ColorType::Rgb);
+        encoder.set_depth(png::BitDepth::Eight);
+        encoder
+            .set_animated(self.frames.len() as u32, self.loop_count)
+            .expect("couldn't set animation");
+        let mut writer = encoder.write_header().expect("failed to write apng header");
+        for (frame, delay_ms) in &self.frames {
+            // Delays over a u16 of milliseconds are kept in hundredths of a second.
+            let (numerator, denominator) = match u16::try_from(*delay_ms) {
+                Ok(ms) => (ms, 1000),
+                Err(_) => ((delay_ms / 10).min(u16::MAX as u32) as u16, 100),
+            };
+            writer
+                .set_frame_delay(numerator, denominator)
+                .expect("couldn't set frame delay");
+            writer
+                .write_image_data(frame)
+                .expect("failed to encode frame");
+        }
+        writer.finish().expect("failed to finish apng");
+    }
+}
+
+struct Webp {
+    file: Output,
+    /// The VP8L bitstream of each frame and its delay.
+    frames: Vec<(Vec<u8>, u32)>,
+    size: (u32, u32),
+    loop_count: u32,
+}
+
+impl AnimationEncoder for Webp {
+    fn add_frame(&mut self, image: &ndarray::Array3<u8>, delay_ms: u32) {
+        let (h, w, _) = image.dim();
+        self.size = (w as u32, h as u32);
+
+        // Encode the frame as a still image, whose only chunk is the VP8L bitstream: "RIFF",
+        // size, "WEBP", "VP8L", size, data.
+        let mut still = Vec::new();
+        image_webp::WebPEncoder::new(&mut still)
+            .encode(
+                &rgb_bytes(image),
+                w as u32,
+                h as u32,
+                image_webp::ColorType::Rgb8,
+            )
+            .expect("failed to encode frame");
+        assert_eq!(&still[12..16], b"VP8L", "unexpected webp chunk");
+        let length = u32::from_le_bytes(still[16..20].try_into().unwrap()) as usize;
+        self.frames
+            .push((still[20..20 + length].to_vec(), delay_ms));
+    }
+
+    fn finish(mut self: Box<Self>) {
+        if self.frames.is_empty() {
+            return;
+        }
+        let (w, h) = self.size;
+        let u24 = |v: u32| v.to_le_bytes()[..3].to_vec();
+
+        let mut chunks = Vec::new();
+        // Extended header: only the animation flag, and the canvas size.
+        let vp8x = [vec![0x02, 0, 0, 0], u24(w - 1), u24(h - 1)].concat();
+        write_chunk(&mut chunks, b"VP8X", &vp8x);
+        // Background color (black) and loop count.
+        let anim = [
+            vec![0, 0, 0, 255],
+            (self.loop_count.min(u16::MAX as u32) as u16)
+                .to_le_bytes()
+                .to_vec(),
+        ]
+        .concat();
+        write_chunk(&mut chunks, b"ANIM", &anim);
+        for (frame, delay_ms) in &self.frames {
+            // Offset, size and duration of the frame, then a flags byte: don't blend with the
+            // previous frame, don't dispose of it.
+            let mut anmf = [
+                u24(0),
+                u24(0),
+                u24(w - 1),
+                u24(h - 1),
+                u24((*delay_ms).min(0xff_ffff)),
+                vec![0x02],
+            ]
+            .concat();
+            write_chunk(&mut anmf, b"VP8L", frame);
+            write_chunk(&mut chunks, b"ANMF", &anmf);
+        }
+
+        self.file.write_all(b"RIFF").unwrap();
+        self.file
+            .write_all(&(4 + chunks.len() as u32).to_le_bytes())
+            .unwrap();
+        self.file.write_all(b"WEBP").unwrap();
+        self.file.write_all(&chunks).expect("failed to write webp");
+        self.file.flush().expect("failed to write webp");
+    }
+}
+
+struct Y4m {
+    file: Output,
+    /// The fixed time between frames, that of the first frame, once the header is written.
+    frame_period_ms: Option<u32>,
+}
+
+impl AnimationEncoder for Y4m {
+    fn add_frame(&mut self, image: &ndarray::Array3<u8>, delay_ms: u32) {
+        let (h, w, _) = image.dim();
+        let frame_period_ms = *self.frame_period_ms.get_or_insert_with(|| {
+            let frame_period_ms = delay_ms.max(1);
+            // Progressive, square pixels, no chroma subsampling.
+            writeln!(
+                self.file,
+                "YUV4MPEG2 W{} H{} F1000:{} Ip A1:1 C444",
+                w, h, frame_period_ms
+            )
+            .expect("failed to write y4m header");
+            frame_period_ms
+        });
+
+        // BT.601 limited range, one plane after the other.
+        let mut planes = vec![0; 3 * w * h];
+        for ((y, x), _) in image.index_axis(ndarray::Axis(2), 0).indexed_iter() {
+            let [r, g, b] = [0, 1, 2].map(|c| image[(y, x, c)] as f64 / 255.0);
+            let k = y * w + x;
+            planes[k] = (16.0 + 65.481 * r + 128.553 * g + 24.966 * b).round() as u8;
+            planes[w * h + k] = (128.0 - 37.797 * r - 74.203 * g + 112.0 * b).round() as u8;
+            planes[2 * w * h + k] = (128.0 + 112.0 * r - 93.786 * g - 18.214 * b).round() as u8;
+        }
+        // The frame rate is fixed, so longer frames are repeated.
+        let repeats = (delay_ms as f64 / frame_period_ms as f64).round() as usize;
+        for _ in 0..repeats.max(1) {
+            self.file
+                .write_all(b"FRAME\n")
+                .and_then(|_| self.file.write_all(&planes))
+                .expect("failed to write y4m frame");
+        }
+    }
+
+    fn finish(mut self: Box<Self>) {
+        self.file.flush().expect("failed to write y4m");
+    }
+}
+
+// A RIFF chunk: name, little endian size, data, padded to an even length.
+fn write_chunk(out: &mut Vec<u8>, name: &[u8; 4], data: &[u8]) {
+    out.extend_from_slice(name);
+    out.extend_from_slice(&(data.len() as u32).to_le_bytes());
+    out.extend_from_slice(data);
+    if data.len() % 2 == 1 {
+        out.push(0);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::io::BufReader;
+    use std::num::NonZeroU16;
+
+    use image_webp::{LoopCount, WebPDecoder};
+
+    use super::*;
+
+    #[test]
+    fn webp_decodes_to_the_frames_delays_and_loop_count_written() {
+        // Width and height differ, to catch them swapped or off by one.
+        let frames: Vec<ndarray::Array3<u8>> = (0..3)
+            .map(|k| {
+                ndarray::Array3::from_shape_fn((7, 5, 3), |(y, x, c)| {
+                    (k * 50 + y * 20 + x * 7 + c) as u8
+                })
+            })
+            .collect();
+        let path = std::env::temp_dir().join(format!("solids-test-{}.webp", std::process::id()));
+        let mut animation = encoder(
+            &path,
+            &AnimParams {
+                format: AnimFormat::Webp,
+                frame_delay_ms: 40,
+                hold_ms: 1000,
+                loop_count: 3,
+            },
+        );
+        for frame in &frames {
+            animation.add_frame(frame);
+        }
+        animation.finish();
+
+        let mut decoder = WebPDecoder::new(BufReader::new(File::open(&path).unwrap())).unwrap();
+        std::fs::remove_file(&path).unwrap();
+        assert!(decoder.is_animated());
+        assert_eq!(decoder.dimensions(), (5, 7));
+        assert_eq!(decoder.num_frames(), 3);
+        assert_eq!(
+            decoder.loop_count(),
+            LoopCount::Times(NonZeroU16::new(3).unwrap())
+        );
+        let mut buffer = vec![0; decoder.output_buffer_size().unwrap()];
+        for (frame, delay_ms) in frames.iter().zip([40, 40, 1040]) {
+            assert_eq!(decoder.read_frame(&mut buffer).unwrap(), delay_ms);
+            assert_eq!(buffer, rgb_bytes(frame));
+        }
+    }
+}
@@ -0,0 +1,154 @@
+//! Bounding volume hierarchy over the triangles of a solid, so a ray is only tested against the
+//! triangles in boxes it passes through.
+//!
+//! Built top down, splitting the triangles at the median centroid along the longest axis of the
+//! centroid bounds until a node holds at most `LEAF_SIZE` triangles.
+
+use nalgebra::{Point3, UnitVector3, Vector3};
+
+use crate::solid::{Locations, Triangles};
+
+const LEAF_SIZE: usize = 4;
+
+struct Node {
+    min: Vector3<f64>,
+    max: Vector3<f64>,
+    // Children for an inner node; for a leaf, `start..start + count` in `Bvh::order`.
+    left: usize,
+    right: usize,
+    start: usize,
+    count: usize,
+}
+
+pub struct Bvh {
+    nodes: Vec<Node>,
+    // Triangle ids, grouped by leaf.
+    order: Vec<usize>,
+}
+
+impl Bvh {
+    pub fn new(locations: &Locations, triangles: &Triangles) -> Self {
+        let mut bvh = Bvh {
+            nodes: Vec::new(),
+            order: (0..triangles.len()).collect(),
+        };
+        let centroids: Vec<Vector3<f64>> = triangles
+            .iter()
+            .map(|tri| {
+                tri.iter()
+                    .map(|id| locations[*id].coords)
+                    .sum::<Vector3<f64>>()
+                    / 3.0
+            })
+            .collect();
+        if !triangles.is_empty() {
+            bvh.build(locations, triangles, &centroids, 0, triangles.len());
+        }
+        bvh
+    }
+
+    // Adds the node for `order[start..end]` and its descendants, and returns its index.
+    fn build(
+        &mut self,
+        locations: &Locations,
+        triangles: &Triangles,
+        centroids: &[Vector3<f64>],
+        start: usize,
+        end: usize,
+    ) -> usize {
+        let (mut min, mut max) = (Vector3::repeat(f64::MAX), Vector3::repeat(f64::MIN));
+        let (mut c_min, mut c_max) = (Vector3::repeat(f64::MAX), Vector3::repeat(f64::MIN));
+        for &t in &self.order[start..end] {
+            for id in triangles[t] {
+                min = min.inf(&locations[id].coords);
+                max = max.sup(&locations[id].coords);
+            }
+            c_min = c_min.inf(&centroids[t]);
+            c_max = c_max.sup(&centroids[t]);
+        }
+
+        let node = self.nodes.len();
+        self.nodes.push(Node {
+            min,
+            max,
+            left: 0,
+            right: 0,
+            start,
+            count: end - start,
+        });
+        if end - start <= LEAF_SIZE {
+            return node;
+        }
+
+        let axis = (c_max - c_min).imax();
+        let mid = (start + end) / 2;
+        self.order[start..end].select_nth_unstable_by(mid - start, |a, b| {
+            centroids[*a][axis].total_cmp(&centroids[*b][axis])
+        });
+
+        let left = self.build(locations, triangles, centroids, start, mid);
+        let right = self.build(locations, triangles, centroids, mid, end);
+        self.nodes[node].left = left;
+        self.nodes[node].right = right;
+        self.nodes[node].count = 0;
+        node
+    }
+
+    /// Calls `hit` for every triangle in a box the ray passes through, nearest boxes first, and
+    /// skips boxes further away than the closest distance `hit` has returned so far.
+    pub fn traverse(
+        &self,
+        ray_source: Point3<f64>,
+        ray_direction: UnitVector3<f64>,
+        mut hit: impl FnMut(usize) -> Option<f64>,
+    ) {
+        if self.nodes.is_empty() {
+            return;
+        }
+
+        let inv_direction = ray_direction.map(|c| 1.0 / c);
+        let mut closest = f64::MAX;
+        let mut stack = vec![0];
+        while let Some(node_id) = stack.pop() {
+            let node = &self.nodes[node_id];
+            match entry_distance(node, &ray_source, &inv_direction) {
+                Some(d) if d <= closest => {}
+                _ => continue,
+            }
+
+            if node.count > 0 {
+                for &t in &self.order[node.start..node.start + node.count] {
+                    if let Some(d) = hit(t) {
+                        closest = closest.min(d);
+                    }
+                }
+                continue;
+            }
+
+            // Visit the nearer child first.
+            let (left, right) = (&self.nodes[node.left], &self.nodes[node.right]);
+            let left_d = entry_distance(left, &ray_source, &inv_direction).unwrap_or(f64::MAX);
+            let right_d = entry_distance(right, &ray_source, &inv_direction).unwrap_or(f64::MAX);
+            if left_d < right_d {
+                stack.push(node.right);
+                stack.push(node.left);
+            } else {
+                stack.push(node.left);
+                stack.push(node.right);
+            }
+        }
+    }
+}
+
+// Distance along the ray to where it enters the box, if it hits it at all (slab method).
+fn entry_distance(node: &Node, source: &Point3<f64>, inv_direction: &Vector3<f64>) -> Option<f64> {
+    let mut t_min: f64 = 0.0;
+    let mut t_max = f64::MAX;
+    for k in 0..3 {
+        let t0 = (node.min[k] - source[k]) * inv_direction[k];
+        let t1 = (node.max[k] - source[k]) * inv_direction[k];
+        t_min = t_min.max(t0.min(t1));
+        t_max = t_max.min(t0.max(t1));
+    }
+    (t_min <= t_max).then_some(t_min)
+}
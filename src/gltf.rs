@@ -0,0 +1,334 @@
+//! glTF 2.0 export of a relaxed solid.
+//!
+//! Writes a single mesh with one primitive. Normals are left out, so viewers shade it flat. The
+//! relaxation can optionally be included as an animation: every snapshot becomes a morph target
+//! of the final mesh, and a weights animation blends from one snapshot to the next, ending on the
+//! final shape. The final triangulation is used for every frame.
+//!
+//! A `.glb` path gives the binary container, anything else a `.gltf` with the buffer embedded as
+//! a data URI.
+
+use std::fs::File;
+use std::io::{BufWriter, Write};
+use std::path::Path;
+
+use crate::solid::{Locations, Solid};
+
+const ARRAY_BUFFER: u32 = 34962;
+const ELEMENT_ARRAY_BUFFER: u32 = 34963;
+const FLOAT: u32 = 5126;
+const UNSIGNED_INT: u32 = 5125;
+
+pub struct GltfParams {
+    /// Seconds between two snapshots in the animation.
+    pub frame_duration: f64,
+}
+
+// Accumulates the binary buffer together with its buffer views and accessors.
+#[derive(Default)]
+struct Buffer {
+    bytes: Vec<u8>,
+    views: Vec<String>,
+    accessors: Vec<String>,
+}
+
+impl Buffer {
+    fn push_view(&mut self, data: &[u8], target: Option<u32>) -> usize {
+        let target = target
+            .map(|t| format!(r#","target":{}"#, t))
+            .unwrap_or_default();
+        self.views.push(format!(
+            r#"{{"buffer":0,"byteOffset":{},"byteLength":{}{}}}"#,
+            self.bytes.len(),
+            data.len(),
+            target
+        ));
+        self.bytes.extend_from_slice(data);
+        self.views.len() - 1
+    }
+
+    /// Adds a VEC3 float accessor, with the min/max bounds glTF requires for positions.
+    fn push_vec3(&mut self, values: &[[f32; 3]]) -> usize {
+        let data: Vec<u8> = values
+            .iter()
+            .flatten()
+            .flat_map(|x| x.to_le_bytes())
+            .collect();
+        let view = self.push_view(&data, Some(ARRAY_BUFFER));
+
+        let mut min = [f32::MAX; 3];
+        let mut max = [f32::MIN; 3];
+        for v in values {
+            for k in 0..3 {
+                min[k] = min[k].min(v[k]);
+                max[k] = max[k].max(v[k]);
+            }
+        }
+        self.accessors.push(format!(
+            r#"{{"bufferView":{},"componentType":{},"count":{},"type":"VEC3","min":{},"max":{}}}"#,
+            view,
+            FLOAT,
+            values.len(),
+            json_floats(&min),
+            json_floats(&max)
+        ));
+        self.accessors.len() - 1
+    }
+
+    fn push_indices(&mut self, indices: &[u32]) -> usize {
+        let data: Vec<u8> = indices.iter().flat_map(|x| x.to_le_bytes()).collect();
+        let view = self.push_view(&data, Some(ELEMENT_ARRAY_BUFFER));
+        self.accessors.push(format!(
+            r#"{{"bufferView":{},"componentType":{},"count":{},"type":"SCALAR"}}"#,
+            view,
+            UNSIGNED_INT,
+            indices.len()
+        ));
+        self.accessors.len() - 1
+    }
+
+    fn push_scalars(&mut self, values: &[f32], bounds: bool) -> usize {
+        let data: Vec<u8> = values.iter().flat_map(|x| x.to_le_bytes()).collect();
+        let view = self.push_view(&data, None);
+        let bounds = if bounds {
+            let min = values.iter().copied().fold(f32::MAX, f32::min);
+            let max = values.iter().copied().fold(f32::MIN, f32::max);
+            format!(r#","min":[{:?}],"max":[{:?}]"#, min, max)
+        } else {
+            String::new()
+        };
+        self.accessors.push(format!(
+            r#"{{"bufferView":{},"componentType":{},"count":{},"type":"SCALAR"{}}}"#,
+            view,
+            FLOAT,
+            values.len(),
+            bounds
+        ));
+        self.accessors.len() - 1
+    }
+}
+
+fn json_floats(values: &[f32]) -> String {
+    let values: Vec<String> = values.iter().map(|x| format!("{:?}", x)).collect();
+    format!("[{}]", values.join(","))
+}
+
+fn base64(bytes: &[u8]) -> String {
+    const ALPHABET: &[u8; 64] = b"ABCDEFGHIJKLMNOPQRSTUVWXYZabcdefghijklmnopqrstuvwxyz0123456789+/";
+
+    let mut out = String::with_capacity(bytes.len().div_ceil(3) * 4);
+    for chunk in bytes.chunks(3) {
+        let b = [
+            chunk[0],
+            *chunk.get(1).unwrap_or(&0),
+            *chunk.get(2).unwrap_or(&0),
+        ];
+        let n = (b[0] as u32) << 16 | (b[1] as u32) << 8 | b[2] as u32;
+        for k in 0..4 {
+            if k <= chunk.len() {
+                out.push(ALPHABET[(n >> (18 - 6 * k) & 0x3f) as usize] as char);
+            } else {
+                out.push('=');
+            }
+        }
+    }
+    out
+}
+
+/// Write `solid` to `path`. If `snapshots` is not empty, it is included as the animation of the
+/// relaxation.
+pub fn to_gltf<P: AsRef<Path>>(
+    name: String,
+    path: P,
+    solid: &Solid,
+    snapshots: &[Locations],
+    params: &GltfParams,
+) -> std::io::Result<()> {
+    let as_f32 = |p: &nalgebra::Point3<f64>| [p.x as f32, p.y as f32, p.z as f32];
+
+    let mut buffer = Buffer::default();
+
+    let positions: Vec<[f32; 3]> = solid.locations.iter().map(as_f32).collect();
+    let position_accessor = buffer.push_vec3(&positions);
+
+    let indices: Vec<u32> = solid
+        .triangles
+        .iter()
+        .flatten()
+        .map(|id| *id as u32)
+        .collect();
+    let index_accessor = buffer.push_indices(&indices);
+
+    // Morph targets are displacements relative to the final locations.
+    let mut targets = Vec::<String>::new();
+    for snapshot in snapshots {
+        let displacements: Vec<[f32; 3]> = snapshot
+            .iter()
+            .zip(solid.locations.iter())
+            .map(|(p, q)| as_f32(&(p - q.coords)))
+            .collect();
+        let accessor = buffer.push_vec3(&displacements);
+        targets.push(format!(r#"{{"POSITION":{}}}"#, accessor));
+    }
+
+    let mut primitive = format!(
+        r#"{{"attributes":{{"POSITION":{}}},"indices":{},"mode":4"#,
+        position_accessor, index_accessor
+    );
+    let mut mesh_extra = String::new();
+    let mut animations = String::new();
+    if !snapshots.is_empty() {
+        let k = snapshots.len();
+        primitive += &format!(r#","targets":[{}]"#, targets.join(","));
+        mesh_extra = format!(r#","weights":{}"#, json_floats(&vec![0.0; k]));
+
+        // Keyframe i shows snapshot i alone; the extra last keyframe has every weight at zero,
+        // which is the final shape.
+        let times: Vec<f32> = (0..=k)
+            .map(|i| (i as f64 * params.frame_duration) as f32)
+            .collect();
+        let mut weights = vec![0.0f32; (k + 1) * k];
+        for i in 0..k {
+            weights[i * k + i] = 1.0;
+        }
+        let input = buffer.push_scalars(&times, true);
+        let output = buffer.push_scalars(&weights, false);
+        animations = format!(
+            r#","animations":[{{"name":"relaxation","channels":[{{"sampler":0,"target":{{"node":0,"path":"weights"}}}}],"samplers":[{{"input":{},"output":{},"interpolation":"LINEAR"}}]}}]"#,
+            input, output
+        );
+    }
+    primitive += "}";
+
+    let binary = path
+        .as_ref()
+        .extension()
+        .is_some_and(|ext| ext.eq_ignore_ascii_case("glb"));
+
+    // Buffers must be 4-byte aligned; every element written above is 4 bytes wide.
+    let uri = if binary {
+        String::new()
+    } else {
+        format!(
+            r#","uri":"data:application/octet-stream;base64,{}""#,
+            base64(&buffer.bytes)
+        )
+    };
+
+    let json = format!(
+        concat!(
+            r#"{{"asset":{{"version":"2.0","generator":"solids"}},"scene":0,"scenes":[{{"nodes":[0]}}],"#,
+            r#""nodes":[{{"mesh":0,"name":"{name}"}}],"meshes":[{{"name":"{name}","primitives":[{primitive}]{mesh_extra}}}],"#,
+            r#""buffers":[{{"byteLength":{byte_length}{uri}}}],"bufferViews":[{views}],"accessors":[{accessors}]{animations}}}"#
+        ),
+        name = name,
+        primitive = primitive,
+        mesh_extra = mesh_extra,
+        byte_length = buffer.bytes.len(),
+        uri = uri,
+        views = buffer.views.join(","),
+        accessors = buffer.accessors.join(","),
+        animations = animations,
+    );
+
+    let file = File::create(path)?;
+    let mut w = BufWriter::new(file);
+
+    if binary {
+        // Chunks are padded to 4 bytes: the JSON with spaces, the binary data with zeros.
+        let mut json = json.into_bytes();
+        json.resize(json.len().next_multiple_of(4), b' ');
+        let mut bin = buffer.bytes;
+        bin.resize(bin.len().next_multiple_of(4), 0);
+
+        let total_length = 12 + 8 + json.len() + 8 + bin.len();
+        w.write_all(b"glTF")?;
+        w.write_all(&2u32.to_le_bytes())?;
+        w.write_all(&(total_length as u32).to_le_bytes())?;
+        w.write_all(&(json.len() as u32).to_le_bytes())?;
+        w.write_all(b"JSON")?;
+        w.write_all(&json)?;
+        w.write_all(&(bin.len() as u32).to_le_bytes())?;
+        w.write_all(b"BIN\0")?;
+        w.write_all(&bin)?;
+    } else {
+        w.write_all(json.as_bytes())?;
+    }
+
+    w.flush()?;
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use nalgebra::Point3;
+
+    use super::*;
+
+    #[test]
+    fn base64_matches_the_rfc_4648_test_vectors() {
+        for (bytes, encoded) in [
+            ("", ""),
+            ("f", "Zg=="),
+            ("fo", "Zm8="),
+            ("foo", "Zm9v"),
+            ("foob", "Zm9vYg=="),
+            ("fooba", "Zm9vYmE="),
+            ("foobar", "Zm9vYmFy"),
+        ] {
+            assert_eq!(base64(bytes.as_bytes()), encoded);
+        }
+        assert_eq!(base64(&[0xfb, 0xff, 0xbf]), "+/+/");
+    }
+
+    #[test]
+    fn glb_chunks_are_aligned_and_add_up_to_the_file() {
+        let locations: Locations = vec![
+            Point3::new(1.0, 1.0, 1.0),
+            Point3::new(1.0, -1.0, -1.0),
+            Point3::new(-1.0, 1.0, -1.0),
+            Point3::new(-1.0, -1.0, 1.0),
+        ];
+        let solid = Solid::new(
+            locations.clone(),
+            vec![[0, 1, 2], [0, 3, 1], [0, 2, 3], [1, 3, 2]],
+        );
+        // One snapshot, so the buffer also holds a morph target and the animation.
+        let snapshots = vec![locations.iter().map(|p| p * 2.0).collect()];
+        let path = std::env::temp_dir().join(format!("solids-test-{}.glb", std::process::id()));
+        to_gltf(
+            "tetrahedron".to_string(),
+            &path,
+            &solid,
+            &snapshots,
+            &GltfParams {
+                frame_duration: 0.1,
+            },
+        )
+        .unwrap();
+        let glb = std::fs::read(&path).unwrap();
+        std::fs::remove_file(&path).unwrap();
+
+        let u32_at =
+            |offset: usize| u32::from_le_bytes(glb[offset..offset + 4].try_into().unwrap());
+        assert_eq!(&glb[0..4], b"glTF");
+        assert_eq!(u32_at(4), 2);
+        assert_eq!(u32_at(8) as usize, glb.len());
+
+        let json_length = u32_at(12) as usize;
+        assert_eq!(&glb[16..20], b"JSON");
+        assert_eq!(json_length % 4, 0);
+        let json = std::str::from_utf8(&glb[20..20 + json_length]).unwrap();
+        assert!(json.starts_with('{') && json.trim_end().ends_with('}'));
+
+        let bin = 20 + json_length;
+        let bin_length = u32_at(bin) as usize;
+        assert_eq!(&glb[bin + 4..bin + 8], b"BIN\0");
+        assert_eq!(bin_length % 4, 0);
+        assert_eq!(bin + 8 + bin_length, glb.len());
+        // 4 positions, 12 indices, 4 displacements, 2 times and 2 weights, 4 bytes each.
+        let byte_length: usize = (4 * 3 + 12 + 4 * 3 + 2 + 2) * 4;
+        assert!(json.contains(&format!(r#""buffers":[{{"byteLength":{}}}]"#, byte_length)));
+        assert_eq!(bin_length, byte_length.next_multiple_of(4));
+    }
+}
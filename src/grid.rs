@@ -0,0 +1,60 @@
+//! Several relaxations side by side: every solid from every seed, each in a labelled tile of one
+//! image.
+
+use crate::platonic_solids::PlatonicSolid;
+
+pub struct GridParams {
+    pub solids: Vec<PlatonicSolid>,
+    /// Seeds of the random starting points, each run with every solid. Empty for one run of each
+    /// solid from a random start.
+    pub seeds: Vec<u64>,
+    /// Tiles per row, or None for a grid as close to square as it gets.
+    pub columns: Option<usize>,
+}
+
+impl GridParams {
+    /// Solid and seed of every tile, row by row.
+    pub fn runs(&self) -> Vec<(PlatonicSolid, Option<u64>)> {
+        let seeds: Vec<Option<u64>> = if self.seeds.is_empty() {
+            vec![None]
+        } else {
+            self.seeds.iter().copied().map(Some).collect()
+        };
+        self.solids
+            .iter()
+            .flat_map(|solid| seeds.iter().map(move |seed| (solid.clone(), *seed)))
+            .collect()
+    }
+
+    pub fn columns(&self, tiles: usize) -> usize {
+        self.columns
+            .unwrap_or_else(|| (tiles as f64).sqrt().ceil() as usize)
+            .clamp(1, tiles.max(1))
+    }
+}
+
+/// The label of the tile of `solid` started from `seed`.
+pub fn label(solid: &PlatonicSolid, seed: Option<u64>) -> String {
+    match seed {
+        Some(seed) => format!("{} #{}", solid, seed),
+        None => solid.to_string(),
+    }
+}
+
+/// `tiles`, which are all the same size, laid out `columns` to a row.
+pub fn tile(tiles: &[ndarray::Array3<u8>], columns: usize) -> ndarray::Array3<u8> {
+    let (h, w, c) = tiles[0].dim();
+    let rows = tiles.len().div_ceil(columns);
+    let mut image = ndarray::Array3::<u8>::zeros((rows * h, columns * w, c));
+    for (k, tile) in tiles.iter().enumerate() {
+        let (row, column) = (k / columns, k % columns);
+        image
+            .slice_mut(ndarray::s![
+                row * h..(row + 1) * h,
+                column * w..(column + 1) * w,
+                ..
+            ])
+            .assign(tile);
+    }
+    image
+}
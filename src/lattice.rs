@@ -0,0 +1,182 @@
+//! Strut-and-node lattice of the graph, for 3D printing the edges rather than the hull.
+//!
+//! Every edge becomes a tube of `segments` sides and every vertex a node hub. The ends of all
+//! tubes meeting at a vertex are rings on a sphere of radius `node_radius` around it, and the
+//! hub is the convex hull of those rings and of more points sampled on the same sphere. Each ring
+//! is then a face of the hub; dropping it and gluing the tube to its edges gives one closed,
+//! manifold mesh without any boolean operations.
+
+use std::f64::consts::PI;
+
+use nalgebra::{Point3, Vector3};
+
+use crate::solid::{Locations, Neighbors, Solid, Triangles, VertexId};
+use crate::triangulate::{coplanar_faces, fan_triangles, hull_triangles};
+use crate::view::perpendicular_basis;
+
+pub struct LatticeParams {
+    /// Radius of the tube around each edge.
+    pub strut_radius: f64,
+    /// Radius of the hub around each vertex. Must be large enough that the tubes meeting at a
+    /// vertex don't overlap before they reach the hub.
+    pub node_radius: f64,
+    /// Number of sides of each tube.
+    pub segments: usize,
+}
+
+// Roughly evenly spaced unit vectors.
+fn fibonacci_sphere(n: usize) -> Vec<Vector3<f64>> {
+    let golden_angle = PI * (3.0 - 5.0_f64.sqrt());
+    (0..n)
+        .map(|k| {
+            let z = 1.0 - 2.0 * (k as f64 + 0.5) / n as f64;
+            let r = (1.0 - z * z).sqrt();
+            let phi = golden_angle * k as f64;
+            Vector3::new(r * phi.cos(), r * phi.sin(), z)
+        })
+        .collect()
+}
+
+/// The lattice of the graph, or what is wrong with `params` if they don't give a valid one.
+pub fn lattice(
+    locations: &Locations,
+    neighbors: &Neighbors,
+    params: &LatticeParams,
+) -> Result<Solid, String> {
+    let LatticeParams {
+        strut_radius,
+        node_radius,
+        segments,
+    } = *params;
+    if segments < 3 {
+        return Err(format!("struts need at least 3 segments, got {}", segments));
+    }
+    if strut_radius <= 0.0 {
+        return Err(format!(
+            "strut radius must be positive, got {}",
+            strut_radius
+        ));
+    }
+    if node_radius <= strut_radius {
+        return Err(format!(
+            "node radius ({}) must be larger than the strut radius ({})",
+            node_radius, strut_radius
+        ));
+    }
+
+    // Distance from a vertex to the plane of the rings around it, and the half angle of the cone
+    // from the vertex to a ring.
+    let offset = (node_radius * node_radius - strut_radius * strut_radius).sqrt();
+    let ring_angle = (strut_radius / node_radius).asin();
+
+    let mut mesh_locations = Locations::new();
+    let mut triangles = Triangles::new();
+
+    // Rings at both ends of every edge; `rings[vertex]` holds the strut direction and the ids of
+    // the ring points for each edge at that vertex.
+    let mut rings: Vec<Vec<(Vector3<f64>, Vec<VertexId>)>> = vec![Vec::new(); locations.len()];
+    for (a, vertex_neighbors) in neighbors.iter().enumerate() {
+        for &b in vertex_neighbors {
+            if b < a {
+                continue;
+            }
+            let (pa, pb) = (locations[a], locations[b]);
+            if nalgebra::distance(&pa, &pb) <= 2.0 * offset {
+                return Err(format!(
+                    "edge ({}, {}) is too short for the node radius, decrease it",
+                    a, b
+                ));
+            }
+
+            let d = (pb - pa).normalize();
+            let (u, v) = perpendicular_basis(&d);
+            let mut ring_at = |center: Point3<f64>| -> Vec<VertexId> {
+                (0..segments)
+                    .map(|k| {
+                        let theta = 2.0 * PI * k as f64 / segments as f64;
+                        mesh_locations
+                            .push(center + strut_radius * (theta.cos() * u + theta.sin() * v));
+                        mesh_locations.len() - 1
+                    })
+                    .collect()
+            };
+            let ring_a = ring_at(pa + offset * d);
+            let ring_b = ring_at(pb - offset * d);
+
+            // Tube between the two rings, wound outward.
+            for k in 0..segments {
+                let l = (k + 1) % segments;
+                for mut tri in [
+                    [ring_a[k], ring_a[l], ring_b[l]],
+                    [ring_a[k], ring_b[l], ring_b[k]],
+                ] {
+                    let [p0, p1, p2] = tri.map(|id| mesh_locations[id]);
+                    let centroid = (p0.coords + p1.coords + p2.coords) / 3.0;
+                    let radial = (centroid - pa.coords) - (centroid - pa.coords).dot(&d) * d;
+                    if (p1 - p0).cross(&(p2 - p0)).dot(&radial) < 0.0 {
+                        tri.swap(1, 2);
+                    }
+                    triangles.push(tri);
+                }
+            }
+
+            rings[a].push((d, ring_a));
+            rings[b].push((-d, ring_b));
+        }
+    }
+
+    // Hubs.
+    let sphere = fibonacci_sphere(segments * segments / 2);
+    let spacing = (4.0 * PI / sphere.len() as f64).sqrt();
+    for (vertex, vertex_rings) in rings.iter().enumerate() {
+        let center = locations[vertex];
+
+        for (k, (d1, _)) in vertex_rings.iter().enumerate() {
+            for (d2, _) in &vertex_rings[k + 1..] {
+                if d1.angle(d2) <= 2.0 * ring_angle {
+                    return Err(format!(
+                        "struts at vertex {} overlap, increase the node radius",
+                        vertex
+                    ));
+                }
+            }
+        }
+
+        // Points of the hub, relative to its center, and their ids in the mesh.
+        let mut hub_ids: Vec<VertexId> = vertex_rings
+            .iter()
+            .flat_map(|(_, ring)| ring.iter().copied())
+            .collect();
+        let mut hub_locations: Locations = hub_ids
+            .iter()
+            .map(|id| Point3::from(mesh_locations[*id] - center))
+            .collect();
+
+        // Sphere points not under any ring, with some margin so no slivers form along it.
+        for s in &sphere {
+            if vertex_rings
+                .iter()
+                .all(|(d, _)| s.angle(d) > ring_angle + 0.5 * spacing)
+            {
+                hub_locations.push(Point3::from(node_radius * s));
+                mesh_locations.push(center + node_radius * s);
+                hub_ids.push(mesh_locations.len() - 1);
+            }
+        }
+
+        let hub_faces = coplanar_faces(&hull_triangles(&hub_locations), &hub_locations);
+        let hub_faces: Vec<Vec<VertexId>> = hub_faces
+            .into_iter()
+            .map(|face| face.iter().map(|id| hub_ids[*id]).collect::<Vec<_>>())
+            // The rings are where the tubes attach.
+            .filter(|face| {
+                !vertex_rings.iter().any(|(_, ring)| {
+                    face.len() == ring.len() && face.iter().all(|id| ring.contains(id))
+                })
+            })
+            .collect();
+        triangles.extend(fan_triangles(&hub_faces));
+    }
+
+    Ok(Solid::new(mesh_locations, triangles))
+}
@@ -1,12 +1,22 @@
 //! Generate the Platonic solids as STL files via constraints.
 
-use std::fs::File;
+use std::collections::BTreeMap;
 use std::path::PathBuf;
-use std::sync::mpsc::channel;
-use std::sync::Arc;
+use std::sync::mpsc::{channel, sync_channel};
+use std::sync::{Arc, Mutex};
 use std::thread;
+use std::time::Instant;
 
+mod animation;
+mod bvh;
+mod gltf;
+mod grid;
+mod lattice;
+mod pacing;
+mod placement;
 mod platonic_solids;
+mod ply;
+mod preview;
 mod relax;
 mod solid;
 mod triangulate;
@@ -17,16 +27,47 @@ use solid::*;
 use triangulate::*;
 
 use clap::{Parser, ValueEnum};
-use image::{codecs::gif::GifEncoder, ExtendedColorType};
+use image::ExtendedColorType;
 use rayon::ThreadPoolBuilder;
+use rayon::prelude::*;
 use strum::Display;
 
 #[derive(Clone, Debug, Display, ValueEnum)]
 pub enum OutputType {
     /// Get a gif of the shape evolving from random point to the final shape. Outputs to `$(pwd)/out.gif` unless `--output` is set.
+    /// An APNG or WebP animation is written instead for `--anim-format` or a `.png`/`.webp` path.
     EvolutionGif,
     /// Get an stl file of the final shape. Outputs to `$(pwd)/<solid>.stl` unless `--output` is set.
     Stl,
+    /// Get a ply file of the final shape, with the attributes from `--ply-attributes`. Outputs to
+    /// `$(pwd)/<solid>.ply` unless `--output` is set.
+    Ply,
+    /// Get a glTF 2.0 file of the final shape, optionally animated with `--gltf-animation`.
+    /// Outputs to `$(pwd)/<solid>.gltf` unless `--output` is set; a `.glb` path writes the binary
+    /// container.
+    Gltf,
+    /// Get a printable lattice of the final shape: a tube for every edge and a hub for every
+    /// vertex. Outputs to `$(pwd)/<solid>-lattice.stl` unless `--output` is set; an `.obj` path
+    /// writes an obj file instead.
+    Lattice,
+    /// Get a gif of the final shape seen from a camera orbiting around it. Outputs to
+    /// `$(pwd)/<solid>-turntable.gif` unless `--output` is set. Takes the same formats as
+    /// EvolutionGif.
+    Turntable,
+    /// Time rendering the final shape and its lattice with and without the bounding volume
+    /// hierarchy, and with the rasterizer.
+    Benchmark,
+    /// Get the frames of the evolution gif as numbered PNGs, `frame_00000.png` onwards. Outputs to
+    /// `$(pwd)/frames/` unless `--output` is set.
+    Frames,
+    /// Get a vector image of the final shape and its graph, as seen from the camera. Outputs to
+    /// `$(pwd)/<solid>.svg` unless `--output` is set.
+    Svg,
+    /// Get an animation of several evolutions side by side, one labelled tile for every solid in
+    /// `--grid-solids` from every seed in `--seeds`, with their frames in step. Outputs to
+    /// `$(pwd)/grid.gif` unless `--output` is set; takes the same formats as EvolutionGif, or
+    /// writes a PNG of the final shapes with `--still`.
+    Grid,
 }
 
 /// Simple program to greet a person
@@ -39,139 +80,1034 @@ struct Args {
     /// What to do?
     #[arg(short, long, default_value_t=OutputType::EvolutionGif)]
     output_type: OutputType,
-    /// Output file path (gif for EvolutionGif, stl for Stl).
+    /// Output file path (gif/png/webp/y4m for EvolutionGif and Turntable, stl for Stl, ply for
+    /// Ply, gltf/glb for Gltf, stl/obj for Lattice, a directory for Frames). `-` writes
+    /// animations to stdout, as Y4M unless --anim-format says otherwise.
     #[arg(short = 'f', long, value_name = "PATH")]
     output: Option<PathBuf>,
+    /// Encoding of the ply file.
+    #[arg(long, default_value_t = ply::PlyFormat::Ascii)]
+    ply_format: ply::PlyFormat,
+    /// Extra per-vertex and per-face properties to write to the ply file.
+    #[arg(long, value_delimiter = ',')]
+    ply_attributes: Vec<ply::PlyAttribute>,
+    /// Include the relaxation as a morph target animation in the glTF file.
+    #[arg(long)]
+    gltf_animation: bool,
+    /// Radius of the lattice struts, in the units of the exported solid: millimetres when it is
+    /// sized with `--edge-length`, `--circumradius` or `--bbox-size`, otherwise the units of the
+    /// relaxation, where edges are about 1 long.
+    #[arg(long, default_value_t = 0.05)]
+    strut_radius: f64,
+    /// Radius of the lattice node hubs. It must leave room for the struts meeting at a vertex to
+    /// part before they reach the hub.
+    #[arg(long, default_value_t = 0.15)]
+    node_radius: f64,
+    /// Number of sides of each lattice strut.
+    #[arg(long, default_value_t = 12)]
+    segments: usize,
+    /// Scale the exported solid so the mean edge length is this many millimetres.
+    #[arg(long, value_name = "MM", group = "size", value_parser = parse_millimetres)]
+    edge_length: Option<f64>,
+    /// Scale the exported solid so its circumradius is this many millimetres.
+    #[arg(long, value_name = "MM", group = "size", value_parser = parse_millimetres)]
+    circumradius: Option<f64>,
+    /// Scale the exported solid so the largest side of its bounding box is this many millimetres.
+    #[arg(long, value_name = "MM", group = "size", value_parser = parse_millimetres)]
+    bbox_size: Option<f64>,
+    /// Rotate the exported solid so it rests on z=0 on this face.
+    #[arg(long, value_name = "FACE")]
+    rest_on_face: Option<usize>,
+    /// Don't write stl/obj files whose mesh fails validation.
+    #[arg(long)]
+    strict: bool,
+    /// Camera projection for rendering.
+    #[arg(long, default_value_t = view::Projection::Orthographic)]
+    projection: view::Projection,
+    /// Vertical field of view of the perspective camera, in degrees.
+    #[arg(long, default_value_t = 25.0, value_parser = parse_fov)]
+    fov: f64,
+    /// Camera position for rendering.
+    #[arg(
+        long,
+        value_parser = view::parse_vector,
+        default_value = "0,0,-10",
+        allow_hyphen_values = true
+    )]
+    eye: nalgebra::Vector3<f64>,
+    /// Point the camera looks at.
+    #[arg(
+        long,
+        value_parser = view::parse_vector,
+        default_value = "0,0,0",
+        allow_hyphen_values = true
+    )]
+    target: nalgebra::Vector3<f64>,
+    /// Direction that is up in the rendered images.
+    #[arg(
+        long,
+        value_parser = view::parse_vector,
+        default_value = "0,1,0",
+        allow_hyphen_values = true
+    )]
+    up: nalgebra::Vector3<f64>,
+    /// How to render images.
+    #[arg(long, default_value_t = view::Renderer::Raytrace)]
+    renderer: view::Renderer,
+    /// Number of frames in a full turn of the turntable.
+    #[arg(long, default_value_t = 60)]
+    frames: usize,
+    /// Light for rendering, as headlight, directional:x,y,z or point:x,y,z, each optionally
+    /// followed by :intensity. Repeat for more lights.
+    #[arg(long = "light", default_value = "headlight")]
+    lights: Vec<view::light::Light>,
+    /// Light reaching every surface, lit or not.
+    #[arg(long, default_value_t = 0.0)]
+    ambient: f64,
+    /// Strength of the diffuse (Lambert) reflection.
+    #[arg(long, default_value_t = 1.0)]
+    diffuse: f64,
+    /// Strength of the specular (Phong) highlights.
+    #[arg(long, default_value_t = 0.0)]
+    specular: f64,
+    /// Sharpness of the specular highlights.
+    #[arg(long, default_value_t = 32.0)]
+    shininess: f64,
+    /// How to color the faces in rendered images.
+    #[arg(long, default_value_t = view::color::ColorScheme::Uniform)]
+    color_scheme: view::color::ColorScheme,
+    /// Colors for the valence and palette color schemes, as #rrggbb.
+    #[arg(
+        long,
+        value_parser = view::color::parse_color,
+        value_delimiter = ',',
+        default_value = "#e6194b,#3cb44b,#ffe119,#4363d8,#f58231,#911eb4"
+    )]
+    palette: Vec<view::color::Color>,
+    /// Draw the edges of the graph over rendered images.
+    #[arg(long)]
+    edges: bool,
+    /// Draw the vertices of the graph over rendered images.
+    #[arg(long)]
+    vertices: bool,
+    /// How to draw edges and vertices hidden behind the solid.
+    #[arg(long, default_value_t = view::overlay::HiddenEdges::Dim)]
+    hidden_edges: view::overlay::HiddenEdges,
+    /// Width of drawn edges, in pixels.
+    #[arg(long, default_value_t = 1.5)]
+    edge_width: f64,
+    /// Radius of drawn vertices, in pixels.
+    #[arg(long, default_value_t = 3.0)]
+    vertex_radius: f64,
+    /// Color of drawn edges and vertices, as #rrggbb.
+    #[arg(long, value_parser = view::color::parse_color, default_value = "#ff8c00")]
+    overlay_color: view::color::Color,
+    /// Make further edges fainter in wireframe renders.
+    #[arg(long)]
+    depth_cue: bool,
+    /// Rays per pixel along each side in the ray tracer, for anti-aliasing.
+    #[arg(long, default_value_t = 1)]
+    samples: usize,
+    /// Where in the pixel to cast the rays when --samples is more than 1.
+    #[arg(long, default_value_t = view::Sampling::Grid)]
+    sampling: view::Sampling,
+    /// How to weight the rays of a pixel when --samples is more than 1.
+    #[arg(long, default_value_t = view::Filter::Box)]
+    filter: view::Filter,
+    /// Stand the solid on a ground plane, perpendicular to --up, in ray traced images.
+    #[arg(long)]
+    ground: bool,
+    /// Cast shadows in ray traced images. A headlight shines along the view rays, so its shadows
+    /// are hidden behind the solid; this needs a directional or point --light.
+    #[arg(long)]
+    shadows: bool,
+    /// Rays per point for ambient occlusion in ray traced images; 0 turns it off. Only darkens
+    /// the --ambient light, which is off by default, so this needs --ambient too.
+    #[arg(long, default_value_t = 0)]
+    occlusion_samples: usize,
+    /// How far away surfaces still occlude ambient light.
+    #[arg(long, default_value_t = 0.5)]
+    occlusion_distance: f64,
+    /// Move and zoom the camera to keep the solid in view. The camera keeps looking along
+    /// --target minus --eye.
+    #[arg(long, default_value_t = view::Framing::None)]
+    framing: view::Framing,
+    /// Format of animations, instead of the one the --output extension names.
+    #[arg(long)]
+    anim_format: Option<animation::AnimFormat>,
+    /// Time each frame of an animation is shown, in milliseconds.
+    #[arg(long, default_value_t = 100, value_parser = clap::value_parser!(u32).range(1..))]
+    frame_delay: u32,
+    /// Frames of an animation per second, instead of --frame-delay. At most 1000.
+    #[arg(long, conflicts_with = "frame_delay", value_parser = parse_fps)]
+    fps: Option<f64>,
+    /// Time the last frame of an animation is shown for on top of the frame delay, in
+    /// milliseconds. Defaults to 2 seconds for the evolution and none for the turntable.
+    #[arg(long)]
+    hold: Option<u32>,
+    /// Number of times an animation plays, 0 for forever. Defaults to twice for the evolution
+    /// and forever for the turntable.
+    #[arg(long)]
+    loop_count: Option<u32>,
+    /// Take a frame of the evolution each time the vertices have moved this far on average,
+    /// instead of every so many steps of the relaxation.
+    #[arg(long, value_parser = parse_movement)]
+    snapshot_movement: Option<f64>,
+    /// Resample the evolution to this many frames, spread evenly over how much the shape changes.
+    #[arg(
+        long,
+        conflicts_with = "duration",
+        value_parser = clap::builder::RangedU64ValueParser::<usize>::new().range(1..)
+    )]
+    target_frames: Option<usize>,
+    /// Resample the evolution to last this many seconds at --frame-delay or --fps, with at least
+    /// one frame.
+    #[arg(long, value_parser = parse_duration)]
+    duration: Option<f64>,
+    /// How the resampled evolution speeds up and slows down.
+    #[arg(long, default_value_t = pacing::Easing::Linear)]
+    easing: pacing::Easing,
+    /// Show the evolution in the terminal, on stderr, as its frames are rendered.
+    #[arg(long)]
+    preview: bool,
+    /// Solids to evolve side by side in the grid, instead of --solid.
+    #[arg(long, value_delimiter = ',')]
+    grid_solids: Vec<PlatonicSolid>,
+    /// Seeds of the random starting points to evolve side by side in the grid, each with every
+    /// solid. A single random start if not given.
+    #[arg(long, value_delimiter = ',')]
+    seeds: Vec<u64>,
+    /// Tiles per row of the grid; as close to square as it gets if not given.
+    #[arg(long)]
+    grid_columns: Option<usize>,
+    /// Write only the final shapes of the grid, as a PNG.
+    #[arg(long)]
+    still: bool,
 }
 
-fn main() {
-    let args = Args::parse();
-    match args.output_type {
-        OutputType::EvolutionGif => evolution(args.solid, args.output),
-        OutputType::Stl => stl(args.solid, args.output),
+fn parse_fov(s: &str) -> Result<f64, String> {
+    let degrees = s.parse::<f64>().map_err(|e| e.to_string())?;
+    if degrees > 0.0 && degrees < 180.0 {
+        Ok(degrees)
+    } else {
+        Err(format!(
+            "expected an angle between 0 and 180 degrees, got {}",
+            s
+        ))
     }
 }
 
-fn evolution(solid: PlatonicSolid, output: Option<PathBuf>) {
-    let (locations_tx, locations_rx) = channel::<Locations>();
-    let (images_tx, images_rx) = channel::<ndarray::Array2<u8>>();
+fn parse_fps(s: &str) -> Result<f64, String> {
+    let fps = s.parse::<f64>().map_err(|e| e.to_string())?;
+    if fps > 0.0 && fps <= 1000.0 {
+        Ok(fps)
+    } else {
+        Err(format!(
+            "expected frames per second in (0, 1000], got {}",
+            s
+        ))
+    }
+}
+
+fn parse_duration(s: &str) -> Result<f64, String> {
+    let seconds = s.parse::<f64>().map_err(|e| e.to_string())?;
+    if seconds > 0.0 && seconds.is_finite() {
+        Ok(seconds)
+    } else {
+        Err(format!("expected a positive number of seconds, got {}", s))
+    }
+}
+
+fn parse_movement(s: &str) -> Result<f64, String> {
+    let distance = s.parse::<f64>().map_err(|e| e.to_string())?;
+    if distance > 0.0 && distance.is_finite() {
+        Ok(distance)
+    } else {
+        Err(format!("expected a positive distance, got {}", s))
+    }
+}
 
-    let view_params = Arc::new(view::ViewParams {
-        camera_center: nalgebra::Point3::new(0.0, 0.0, -10.0),
+fn parse_millimetres(s: &str) -> Result<f64, String> {
+    let mm = s.parse::<f64>().map_err(|e| e.to_string())?;
+    if mm > 0.0 && mm.is_finite() {
+        Ok(mm)
+    } else {
+        Err(format!(
+            "expected a positive size in millimetres, got {}",
+            s
+        ))
+    }
+}
+
+fn view_params(args: &Args) -> view::ViewParams {
+    let mut view_params = view::ViewParams {
+        camera_center: nalgebra::Point3::origin(),
         camera_normal: nalgebra::Vector3::z_axis(),
+        camera_up: nalgebra::Vector3::y(),
+        projection: args.projection,
+        fov_deg: args.fov,
         image_width_px: 400,
         image_height_px: 400,
         pixel_size: 0.01,
-    });
+        renderer: args.renderer,
+        lighting: view::light::Lighting {
+            lights: args.lights.clone(),
+            ambient: args.ambient,
+            diffuse: args.diffuse,
+            specular: args.specular,
+            shininess: args.shininess,
+        },
+        overlay: view::overlay::OverlayParams {
+            edges: args.edges,
+            vertices: args.vertices,
+            hidden: args.hidden_edges,
+            edge_width: args.edge_width,
+            vertex_radius: args.vertex_radius,
+            dash_length: 4.0,
+            color: args.overlay_color,
+        },
+        depth_cue: args.depth_cue,
+        samples: args.samples,
+        sampling: args.sampling,
+        filter: args.filter,
+        ground: args.ground,
+        shadows: args.shadows,
+        occlusion: view::scene::Occlusion {
+            samples: args.occlusion_samples,
+            distance: args.occlusion_distance,
+        },
+        framing: args.framing,
+    };
+    if args.eye == args.target {
+        eprintln!("the camera can't look at its own position, move --eye or --target");
+        std::process::exit(1);
+    }
+    if args.up == nalgebra::Vector3::zeros() {
+        eprintln!("--up must not be zero");
+        std::process::exit(1);
+    }
+    view_params.look_at(args.eye.into(), args.target.into(), args.up);
+
+    if args.shadows
+        && args
+            .lights
+            .iter()
+            .all(|light| matches!(light, view::light::Light::Headlight { .. }))
+    {
+        eprintln!("warning: --shadows has no visible effect with only a headlight, add a --light");
+    }
+    if args.occlusion_samples > 0 && args.ambient <= 0.0 {
+        eprintln!("warning: --occlusion-samples has no effect without --ambient light");
+    }
+    view_params
+}
+
+fn main() {
+    let args = Args::parse();
+    let size = None
+        .or(args.edge_length.map(placement::Size::EdgeLength))
+        .or(args.circumradius.map(placement::Size::Circumradius))
+        .or(args.bbox_size.map(placement::Size::BoundingBox));
+    let placement_params = placement::PlacementParams {
+        size,
+        rest_on_face: args.rest_on_face,
+    };
+    let view_params = view_params(&args);
+    let coloring = view::color::Coloring {
+        scheme: args.color_scheme,
+        palette: args.palette,
+    };
+    let anim_format = animation::AnimFormat::for_path(args.anim_format, args.output.as_deref());
+    let frame_delay_ms = args
+        .fps
+        .map_or(args.frame_delay, |fps| (1000.0 / fps).round() as u32);
+    let anim_params = |default_hold_ms: u32, default_loop_count: u32| animation::AnimParams {
+        format: anim_format,
+        frame_delay_ms,
+        hold_ms: args.hold.unwrap_or(default_hold_ms),
+        loop_count: args.loop_count.unwrap_or(default_loop_count),
+    };
+    let pacing_params = pacing::PacingParams {
+        snapshot_movement: args.snapshot_movement,
+        frames: args.target_frames.or(args
+            .duration
+            .map(|seconds| ((seconds * 1000.0 / frame_delay_ms as f64).round() as usize).max(1))),
+        easing: args.easing,
+    };
+    match args.output_type {
+        OutputType::EvolutionGif => evolution(
+            args.solid,
+            args.output,
+            view_params,
+            coloring,
+            pacing_params,
+            args.preview,
+            Some(anim_params(2000, 2)),
+        ),
+        OutputType::Frames => evolution(
+            args.solid,
+            args.output,
+            view_params,
+            coloring,
+            pacing_params,
+            args.preview,
+            None,
+        ),
+        OutputType::Stl => stl(args.solid, args.output, &placement_params, args.strict),
+        OutputType::Ply => ply(
+            args.solid,
+            args.output,
+            &placement_params,
+            ply::PlyParams {
+                format: args.ply_format,
+                attributes: args.ply_attributes,
+            },
+        ),
+        OutputType::Gltf => gltf(
+            args.solid,
+            args.output,
+            &placement_params,
+            args.gltf_animation,
+        ),
+        OutputType::Lattice => lattice(
+            args.solid,
+            args.output,
+            &placement_params,
+            args.strict,
+            lattice::LatticeParams {
+                strut_radius: args.strut_radius,
+                node_radius: args.node_radius,
+                segments: args.segments,
+            },
+        ),
+        OutputType::Turntable => turntable(
+            args.solid,
+            args.output,
+            view_params,
+            coloring,
+            args.target.into(),
+            args.frames,
+            anim_params(0, 0),
+        ),
+        OutputType::Grid => grid(
+            args.output,
+            view_params,
+            coloring,
+            pacing_params,
+            grid::GridParams {
+                solids: if args.grid_solids.is_empty() {
+                    vec![args.solid]
+                } else {
+                    args.grid_solids
+                },
+                seeds: args.seeds,
+                columns: args.grid_columns,
+            },
+            (!args.still).then(|| anim_params(2000, 2)),
+        ),
+        OutputType::Svg => svg(args.solid, args.output, view_params, coloring),
+        OutputType::Benchmark => benchmark(
+            args.solid,
+            view_params,
+            lattice::LatticeParams {
+                strut_radius: args.strut_radius,
+                node_radius: args.node_radius,
+                segments: args.segments,
+            },
+        ),
+    }
+}
+
+fn evolution(
+    solid: PlatonicSolid,
+    output: Option<PathBuf>,
+    view_params: view::ViewParams,
+    coloring: view::color::Coloring,
+    pacing_params: pacing::PacingParams,
+    preview: bool,
+    // How to encode the frames, or None to write them as PNGs.
+    anim_params: Option<animation::AnimParams>,
+) {
+    let as_frames = anim_params.is_none();
+    let (locations_tx, locations_rx) = channel::<Locations>();
+    let (images_tx, images_rx) = channel::<(usize, ndarray::Array3<u8>)>();
+
+    let mut view_params = view_params;
+    let coloring = Arc::new(coloring);
 
     let relax_params = relax::RelaxParams {
-        spring_constant: 1.0,
-        repulsion_constant: 0.1,
-        natural_length: 1.0,
-        step_size: 1e-4,
-        total_movement_thresh: 1e-7,
         snapshot_period: 5_000,
+        snapshot_movement: pacing_params.snapshot_movement,
         locations_tx: Some(locations_tx),
+        ..Default::default()
     };
 
     // Thread for evolving the shape.
-    let neighbors = neighbors_for_solid(&solid);
-    thread::spawn(move || {
-        relax::relax(&neighbors, relax_params);
-    });
-
-    // Thread for encoding frames into a gif.
-    let encoder_handle = {
-        let output = output.unwrap_or_else(|| PathBuf::from("out.gif"));
-        let gif_file = File::create(&output).expect("failed to create gif output file");
-        let mut gif_encoder = GifEncoder::new_with_speed(gif_file, 10);
-        gif_encoder
-            .set_repeat(image::codecs::gif::Repeat::Finite(1))
-            .expect("couldn't set repeat");
-
-        let vp = Arc::clone(&view_params);
+    let neighbors = Arc::new(neighbors_for_solid(&solid));
+    {
+        let neighbors = Arc::clone(&neighbors);
         thread::spawn(move || {
-            let w = vp.image_width_px as u32;
-            let h = vp.image_height_px as u32;
+            relax::relax(&neighbors, relax_params);
+        });
+    }
 
-            while let Ok(image) = images_rx.recv() {
-                add_frame(image, w, h, &mut gif_encoder);
+    // Fitting the camera to the whole run or resampling it needs all of it before the first
+    // frame.
+    let snapshots: Box<dyn Iterator<Item = Locations>> =
+        if view_params.framing == view::Framing::WholeRun || pacing_params.frames.is_some() {
+            let mut snapshots: Vec<Locations> = locations_rx.iter().collect();
+            if let Some(frames) = pacing_params.frames {
+                snapshots = pacing::resample(&snapshots, frames, pacing_params.easing);
             }
-        })
-    };
+            if view_params.framing == view::Framing::WholeRun {
+                let (center, radius) = view::bounding_sphere(&snapshots);
+                view_params.frame(center, radius);
+            }
+            Box::new(snapshots.into_iter())
+        } else {
+            Box::new(locations_rx.into_iter())
+        };
+    let view_params = Arc::new(view_params);
+
+    // Directory for PNG frames, which the rendering pool writes to directly.
+    let frames_dir = as_frames.then(|| {
+        let dir = output.clone().unwrap_or_else(|| PathBuf::from("frames"));
+        std::fs::create_dir_all(&dir).expect("failed to create frames directory");
+        Arc::new(dir)
+    });
+
+    let preview = preview.then(|| Arc::new(Mutex::new(preview::Preview::default())));
 
     // Rendering pool.
     let pool = ThreadPoolBuilder::new()
         .build()
         .expect("failed to build thread pool.");
 
-    while let Ok(locations) = locations_rx.recv() {
+    // Frames finish in whatever order, so the encoder holds on to them until the ones before
+    // them are in. A frame takes a permit before it is rendered and gives it back once encoded,
+    // which bounds how many frames can be held.
+    let (permits_tx, permits_rx) = sync_channel::<()>(2 * pool.current_num_threads());
+
+    // Thread for encoding frames into an animation.
+    let mut images_rx = Some(images_rx);
+    let encoder_handle = anim_params.map(|anim_params| {
+        let images_rx = images_rx.take().unwrap();
+        let output = output
+            .unwrap_or_else(|| PathBuf::from(format!("out.{}", anim_params.format.extension())));
+        let mut encoder = animation::encoder(&output, &anim_params);
+
+        thread::spawn(move || {
+            let mut pending = BTreeMap::new();
+            let mut next_index = 0;
+            while let Ok((index, image)) = images_rx.recv() {
+                pending.insert(index, image);
+                while let Some(image) = pending.remove(&next_index) {
+                    encoder.add_frame(&image);
+                    permits_rx.recv().unwrap();
+                    next_index += 1;
+                }
+            }
+            assert!(pending.is_empty(), "frame {} never arrived", next_index);
+            encoder.finish();
+        })
+    });
+
+    for (index, locations) in snapshots.enumerate() {
+        if !as_frames {
+            permits_tx.send(()).unwrap();
+        }
         let vp = Arc::clone(&view_params);
+        let coloring = Arc::clone(&coloring);
+        let neighbors = Arc::clone(&neighbors);
+        let frames_dir = frames_dir.clone();
+        let preview = preview.clone();
         let tx = images_tx.clone();
 
         pool.spawn_fifo(move || {
-            let triangles = hull_triangles(&locations);
-            let solid = solid::Solid {
-                locations,
-                triangles,
-            };
-            let image = view::view(&solid, &vp);
-            tx.send(image).unwrap();
+            let mut vp = (*vp).clone();
+            if vp.framing == view::Framing::PerFrame {
+                let (center, radius) = view::bounding_sphere([&locations]);
+                vp.frame(center, radius);
+            }
+            let solid = colored_hull(locations, &neighbors, &coloring, &vp);
+            let image = view::render(&solid, &neighbors, &vp);
+            if let Some(preview) = preview {
+                preview.lock().unwrap().show(index, &image);
+            }
+            match frames_dir {
+                Some(dir) => save_png(&image, &dir.join(format!("frame_{:05}.png", index))),
+                None => tx.send((index, image)).unwrap(),
+            }
         });
     }
 
     drop(images_tx);
-    encoder_handle.join().expect("gif encoder thread failed");
+    if let Some(encoder_handle) = encoder_handle {
+        encoder_handle
+            .join()
+            .expect("animation encoder thread failed");
+    }
+    if let Some(images_rx) = images_rx {
+        // Nothing is sent when writing PNGs, but every job holds a sender, so the channel only
+        // closes once the pool has written every frame.
+        while images_rx.recv().is_ok() {}
+    }
 }
 
-fn stl(solid_type: PlatonicSolid, output: Option<PathBuf>) {
-    let relax_params = relax::RelaxParams {
-        spring_constant: 1.0,
-        repulsion_constant: 0.1,
-        natural_length: 1.0,
-        step_size: 1e-4,
-        total_movement_thresh: 1e-7,
-        snapshot_period: 10_000,
-        locations_tx: None,
+fn grid(
+    output: Option<PathBuf>,
+    view_params: view::ViewParams,
+    coloring: view::color::Coloring,
+    pacing_params: pacing::PacingParams,
+    grid_params: grid::GridParams,
+    // How to encode the frames, or None to write only the last one as a PNG.
+    anim_params: Option<animation::AnimParams>,
+) {
+    let runs = grid_params.runs();
+
+    // Relax every run on its own thread, keeping all the snapshots.
+    let snapshots: Vec<(Neighbors, Vec<Locations>)> = thread::scope(|scope| {
+        let handles: Vec<_> = runs
+            .iter()
+            .map(|(solid, seed)| {
+                scope.spawn(move || {
+                    let (locations_tx, locations_rx) = channel::<Locations>();
+                    let relax_params = relax::RelaxParams {
+                        snapshot_period: 5_000,
+                        snapshot_movement: pacing_params.snapshot_movement,
+                        seed: *seed,
+                        locations_tx: Some(locations_tx),
+                        ..Default::default()
+                    };
+                    let neighbors = neighbors_for_solid(solid);
+                    relax::relax(&neighbors, relax_params);
+                    (neighbors, locations_rx.iter().collect())
+                })
+            })
+            .collect();
+        handles
+            .into_iter()
+            .map(|handle| handle.join().expect("relaxation thread failed"))
+            .collect()
+    });
+
+    // Keep the runs in step by resampling them all to the same number of frames.
+    let frames = pacing_params.frames.unwrap_or_else(|| {
+        snapshots
+            .iter()
+            .map(|(_, run)| run.len())
+            .max()
+            .unwrap_or(0)
+    });
+    let tiles: Vec<(Neighbors, Vec<Locations>, view::ViewParams)> = snapshots
+        .into_iter()
+        .map(|(neighbors, run)| {
+            let run = pacing::resample(&run, frames, pacing_params.easing);
+            let mut vp = view_params.clone();
+            if vp.framing == view::Framing::WholeRun {
+                let (center, radius) = view::bounding_sphere(&run);
+                vp.frame(center, radius);
+            }
+            (neighbors, run, vp)
+        })
+        .collect();
+    let columns = grid_params.columns(tiles.len());
+
+    // The grid at `frame`, where runs that are already over show their final shape.
+    let render_frame = |frame: usize| {
+        let images: Vec<ndarray::Array3<u8>> = tiles
+            .par_iter()
+            .zip(runs.par_iter())
+            .map(|((neighbors, run, vp), (solid, seed))| {
+                let locations = run[frame.min(run.len() - 1)].clone();
+                let mut vp = vp.clone();
+                if vp.framing == view::Framing::PerFrame {
+                    let (center, radius) = view::bounding_sphere([&locations]);
+                    vp.frame(center, radius);
+                }
+                let solid_hull = colored_hull(locations, neighbors, &coloring, &vp);
+                let mut image = view::render(&solid_hull, neighbors, &vp);
+                view::text::draw_text(
+                    &mut image,
+                    8,
+                    8,
+                    &grid::label(solid, *seed),
+                    2,
+                    &vp.overlay.color,
+                );
+                image
+            })
+            .collect();
+        grid::tile(&images, columns)
     };
 
-    // Thread for evolving the shape.
+    match anim_params {
+        Some(anim_params) => {
+            let output = output.unwrap_or_else(|| {
+                PathBuf::from(format!("grid.{}", anim_params.format.extension()))
+            });
+            let mut encoder = animation::encoder(&output, &anim_params);
+            for frame in 0..frames {
+                encoder.add_frame(&render_frame(frame));
+            }
+            encoder.finish();
+        }
+        None => {
+            let output = output.unwrap_or_else(|| PathBuf::from("grid.png"));
+            save_png(&render_frame(frames.saturating_sub(1)), &output);
+        }
+    }
+}
+
+fn turntable(
+    solid_type: PlatonicSolid,
+    output: Option<PathBuf>,
+    mut view_params: view::ViewParams,
+    coloring: view::color::Coloring,
+    mut target: nalgebra::Point3<f64>,
+    frames: usize,
+    anim_params: animation::AnimParams,
+) {
+    let relax_params = relax::RelaxParams::default();
+
     let neighbors = neighbors_for_solid(&solid_type);
     let locations = relax::relax(&neighbors, relax_params);
+    let solid = colored_hull(locations, &neighbors, &coloring, &view_params);
 
+    // The solid doesn't change, so fitting it once fits every frame, as long as the camera orbits
+    // its center.
+    if view_params.framing != view::Framing::None {
+        let (center, radius) = view::bounding_sphere([&solid.locations]);
+        view_params.frame(center, radius);
+        target = center;
+    }
+
+    // Orbit the camera around the up axis through the target.
+    let eye = view_params.camera_center;
+    let up = view_params.camera_up;
+    let images: Vec<ndarray::Array3<u8>> = (0..frames)
+        .into_par_iter()
+        .map(|frame| {
+            let angle = 2.0 * std::f64::consts::PI * frame as f64 / frames as f64;
+            let rotation =
+                nalgebra::Rotation3::from_axis_angle(&nalgebra::Unit::new_normalize(up), angle);
+            let mut vp = view_params.clone();
+            vp.look_at(target + rotation * (eye - target), target, up);
+            view::render(&solid, &neighbors, &vp)
+        })
+        .collect();
+
+    let output = output.unwrap_or_else(|| {
+        let mut path = std::env::current_dir().unwrap();
+        path.push(format!(
+            "{}-turntable.{}",
+            solid_type,
+            anim_params.format.extension()
+        ));
+        path
+    });
+    let mut encoder = animation::encoder(&output, &anim_params);
+    for image in images {
+        encoder.add_frame(&image);
+    }
+    encoder.finish();
+}
+
+fn svg(
+    solid_type: PlatonicSolid,
+    output: Option<PathBuf>,
+    mut view_params: view::ViewParams,
+    coloring: view::color::Coloring,
+) {
+    let relax_params = relax::RelaxParams::default();
+
+    let neighbors = neighbors_for_solid(&solid_type);
+    let locations = relax::relax(&neighbors, relax_params);
+    let solid = colored_hull(locations, &neighbors, &coloring, &view_params);
+    if view_params.framing != view::Framing::None {
+        let (center, radius) = view::bounding_sphere([&solid.locations]);
+        view_params.frame(center, radius);
+    }
+
+    let output = output.unwrap_or_else(|| {
+        let mut path = std::env::current_dir().unwrap();
+        path.push(format!("{}.svg", solid_type));
+        path
+    });
+    std::fs::write(&output, view::svg::svg(&solid, &neighbors, &view_params))
+        .expect("failed to write svg");
+}
+
+fn benchmark(
+    solid_type: PlatonicSolid,
+    mut view_params: view::ViewParams,
+    lattice_params: lattice::LatticeParams,
+) {
+    // Only the renderers are compared, the lattice has no graph to draw over it, and the
+    // rasterizer casts no rays to supersample.
+    view_params.overlay.edges = false;
+    view_params.overlay.vertices = false;
+    view_params.samples = 1;
+
+    let relax_params = relax::RelaxParams::default();
+
+    let neighbors = neighbors_for_solid(&solid_type);
+    let locations = relax::relax(&neighbors, relax_params);
+    let lattice = lattice::lattice(&locations, &neighbors, &lattice_params).unwrap_or_else(|e| {
+        eprintln!("can't build the lattice: {}", e);
+        std::process::exit(1);
+    });
     let triangles = hull_triangles(&locations);
-    save_stl(
-        &solid_type,
-        &solid::Solid {
-            locations,
-            triangles,
-        },
-        output,
+    let hull = solid::Solid::new(locations, triangles);
+
+    for (name, solid) in [("hull", &hull), ("lattice", &lattice)] {
+        let start = Instant::now();
+        let brute_force_image = view::view(&solid::BruteForce(solid), &view_params);
+        let brute_force_time = start.elapsed();
+
+        let start = Instant::now();
+        let bvh_image = view::view(solid, &view_params);
+        let bvh_time = start.elapsed();
+
+        let start = Instant::now();
+        let raster_image = view::render(
+            solid,
+            &Neighbors::new(),
+            &view::ViewParams {
+                renderer: view::Renderer::Raster,
+                ..view_params.clone()
+            },
+        );
+        let raster_time = start.elapsed();
+
+        let differing_pixels = |image: &ndarray::Array3<u8>| {
+            brute_force_image
+                .rows()
+                .into_iter()
+                .zip(image.rows())
+                .filter(|(a, b)| a != b)
+                .count()
+        };
+        println!(
+            "{} {}: {} triangles, brute force {:.3?}, bvh {:.3?} ({:.1}x faster), {} pixels differ",
+            solid_type,
+            name,
+            solid.triangles.len(),
+            brute_force_time,
+            bvh_time,
+            brute_force_time.as_secs_f64() / bvh_time.as_secs_f64(),
+            differing_pixels(&bvh_image)
+        );
+        println!(
+            "{} {}: raster {:.3?} ({:.1}x faster), {} pixels differ",
+            solid_type,
+            name,
+            raster_time,
+            brute_force_time.as_secs_f64() / raster_time.as_secs_f64(),
+            differing_pixels(&raster_image)
+        );
+    }
+}
+
+fn stl(
+    solid_type: PlatonicSolid,
+    output: Option<PathBuf>,
+    placement_params: &placement::PlacementParams,
+    strict: bool,
+) {
+    let relax_params = relax::RelaxParams::default();
+
+    // Thread for evolving the shape.
+    let neighbors = neighbors_for_solid(&solid_type);
+    let mut locations = relax::relax(&neighbors, relax_params);
+
+    // The hull is wound outwards from the origin, so it's found before the solid is moved off it.
+    // Rotating and scaling keep the winding.
+    let triangles = hull_triangles(&locations);
+    let transform = place(&locations, &neighbors, placement_params);
+    placement::transform(&mut locations, &transform);
+
+    let solid = solid::Solid::new(locations, triangles);
+    check_mesh(&solid, 2, strict);
+    save_stl(&solid_type, &solid, output);
+}
+
+fn ply(
+    solid_type: PlatonicSolid,
+    output: Option<PathBuf>,
+    placement_params: &placement::PlacementParams,
+    ply_params: ply::PlyParams,
+) {
+    let relax_params = relax::RelaxParams::default();
+    let (spring_constant, repulsion_constant, natural_length) = (
+        relax_params.spring_constant,
+        relax_params.repulsion_constant,
+        relax_params.natural_length,
+    );
+
+    let neighbors = neighbors_for_solid(&solid_type);
+    let mut locations = relax::relax(&neighbors, relax_params);
+
+    // Whatever force is left over once the relaxation stops.
+    let forces = relax::net_forces(
+        &neighbors,
+        &locations,
+        spring_constant,
+        repulsion_constant,
+        natural_length,
     );
+
+    // Before the solid leaves the origin, as in `stl`.
+    let triangles = hull_triangles(&locations);
+    let faces = merge_faces(&triangles, &locations, &neighbors);
+    let transform = place(&locations, &neighbors, placement_params);
+    placement::transform(&mut locations, &transform);
+
+    let path = output.unwrap_or_else(|| {
+        let mut path = std::env::current_dir().unwrap();
+        path.push(format!("{}.ply", solid_type));
+        path
+    });
+    ply::to_ply(path, &locations, &faces, &neighbors, &forces, &ply_params).unwrap();
 }
 
-fn add_frame<W: std::io::Write>(
-    image: ndarray::Array2<u8>,
-    w: u32,
-    h: u32,
-    gif_encoder: &mut GifEncoder<W>,
+fn gltf(
+    solid_type: PlatonicSolid,
+    output: Option<PathBuf>,
+    placement_params: &placement::PlacementParams,
+    animation: bool,
 ) {
-    let luma: Vec<u8> = match image.as_standard_layout().as_slice() {
-        Some(slice) => slice.to_vec(),
-        None => image.iter().copied().collect(),
+    let (locations_tx, locations_rx) = channel::<Locations>();
+
+    let relax_params = relax::RelaxParams {
+        snapshot_period: 5_000,
+        locations_tx: animation.then_some(locations_tx),
+        ..Default::default()
     };
 
-    // Expand L8 -> Rgb8
-    let mut rgb = Vec::with_capacity(luma.len() * 3);
-    for y in luma {
-        rgb.push(y);
-        rgb.push(y);
-        rgb.push(y);
+    let neighbors = neighbors_for_solid(&solid_type);
+    let mut locations = relax::relax(&neighbors, relax_params);
+    let mut snapshots: Vec<Locations> = locations_rx.iter().collect();
+
+    // Before the solid leaves the origin, as in `stl`.
+    let triangles = hull_triangles(&locations);
+    let transform = place(&locations, &neighbors, placement_params);
+    placement::transform(&mut locations, &transform);
+    for snapshot in snapshots.iter_mut() {
+        placement::transform(snapshot, &transform);
     }
 
-    gif_encoder
-        .encode(&rgb, w, h, ExtendedColorType::Rgb8)
-        .expect("failed to encode frame");
+    let path = output.unwrap_or_else(|| {
+        let mut path = std::env::current_dir().unwrap();
+        path.push(format!("{}.gltf", solid_type));
+        path
+    });
+    gltf::to_gltf(
+        solid_type.to_string(),
+        path,
+        &solid::Solid::new(locations, triangles),
+        &snapshots,
+        &gltf::GltfParams {
+            frame_duration: 0.1,
+        },
+    )
+    .unwrap();
+}
+
+fn lattice(
+    solid_type: PlatonicSolid,
+    output: Option<PathBuf>,
+    placement_params: &placement::PlacementParams,
+    strict: bool,
+    lattice_params: lattice::LatticeParams,
+) {
+    let relax_params = relax::RelaxParams::default();
+
+    let neighbors = neighbors_for_solid(&solid_type);
+    let mut locations = relax::relax(&neighbors, relax_params);
+
+    let transform = place(&locations, &neighbors, placement_params);
+    placement::transform(&mut locations, &transform);
+
+    let mut lattice =
+        lattice::lattice(&locations, &neighbors, &lattice_params).unwrap_or_else(|e| {
+            eprintln!("can't build the lattice: {}", e);
+            std::process::exit(1);
+        });
+    if placement_params.rest_on_face.is_some() {
+        // The hubs and struts stick out below the vertices.
+        placement::drop_to_floor(&mut lattice.locations);
+    }
+
+    // The lattice is a closed surface with one handle per independent cycle of the graph.
+    let edges = neighbors.iter().map(Vec::len).sum::<usize>() / 2;
+    let genus = edges as i64 - neighbors.len() as i64 + 1;
+    check_mesh(&lattice, 2 - 2 * genus, strict);
+
+    let path = output.unwrap_or_else(|| {
+        let mut path = std::env::current_dir().unwrap();
+        path.push(format!("{}-lattice.stl", solid_type));
+        path
+    });
+    let name = format!("{}-lattice", solid_type);
+    if path
+        .extension()
+        .is_some_and(|ext| ext.eq_ignore_ascii_case("obj"))
+    {
+        to_obj(name, &path, &lattice.triangles, &lattice.locations).unwrap();
+    } else {
+        to_stl(name, &path, &lattice.triangles, &lattice.locations).unwrap();
+    }
+}
+
+fn save_png(image: &ndarray::Array3<u8>, path: &std::path::Path) {
+    let (h, w, _) = image.dim();
+    let rgb: Vec<u8> = image.iter().copied().collect();
+    image::save_buffer(path, &rgb, w as u32, h as u32, ExtendedColorType::Rgb8)
+        .expect("failed to write png frame");
+}
+
+// The hull of `locations`, with its faces colored, unless it won't be rendered.
+fn colored_hull(
+    locations: Locations,
+    neighbors: &Neighbors,
+    coloring: &view::color::Coloring,
+    view_params: &view::ViewParams,
+) -> Solid {
+    if view_params.renderer == view::Renderer::Wireframe {
+        return solid::Solid::new(locations, Triangles::new());
+    }
+    let triangles = hull_triangles(&locations);
+    let colors = coloring.triangle_colors(&triangles, &locations, neighbors);
+    let mut solid = solid::Solid::new(locations, triangles);
+    solid.colors = colors;
+    solid
+}
+
+// The transform that places the solid as asked, or give up if it can't be placed that way.
+fn place(
+    locations: &Locations,
+    neighbors: &Neighbors,
+    placement_params: &placement::PlacementParams,
+) -> nalgebra::Similarity3<f64> {
+    placement::placement(locations, neighbors, placement_params).unwrap_or_else(|e| {
+        eprintln!("can't place the solid: {}", e);
+        std::process::exit(1);
+    })
+}
+
+// Print the mesh report, and give up if the mesh has problems and `strict` is set.
+fn check_mesh(solid: &Solid, expected_euler_characteristic: i64, strict: bool) {
+    let report = validate(
+        &solid.triangles,
+        &solid.locations,
+        expected_euler_characteristic,
+    );
+    eprintln!("{}", report);
+    if strict && !report.is_valid() {
+        eprintln!("mesh failed validation, not writing it (--strict)");
+        std::process::exit(1);
+    }
 }
 
 fn save_stl(platonic_solid: &PlatonicSolid, solid: &Solid, output: Option<PathBuf>) {
@@ -0,0 +1,86 @@
+//! Choosing which moments of the relaxation become frames of an animation.
+//!
+//! The relaxation moves fast at first and then crawls towards the final shape, so frames taken
+//! every so many steps spend most of the animation on a shape that barely changes. Frames can
+//! instead be taken each time the shape has moved a given amount, or a run can be resampled to a
+//! set number of frames spread evenly over how much the shape changes.
+
+use clap::ValueEnum;
+use strum::Display;
+
+use crate::solid::Locations;
+
+#[derive(Clone, Copy, Debug, Display, PartialEq, ValueEnum)]
+#[strum(serialize_all = "kebab-case")]
+pub enum Easing {
+    /// The shape changes at a constant rate.
+    Linear,
+    /// Fast at first, slowing down towards the final shape.
+    EaseOut,
+    /// Slow at the start and the end, fast in the middle.
+    EaseInOut,
+}
+
+impl Easing {
+    /// Fraction of the change done at fraction `t` of the animation.
+    fn apply(&self, t: f64) -> f64 {
+        match self {
+            Easing::Linear => t,
+            Easing::EaseOut => 1.0 - (1.0 - t).powi(2),
+            Easing::EaseInOut => t * t * (3.0 - 2.0 * t),
+        }
+    }
+}
+
+pub struct PacingParams {
+    /// Take a snapshot each time the vertices have moved this far on average, instead of every so
+    /// many steps.
+    pub snapshot_movement: Option<f64>,
+    /// Resample the snapshots to this many frames.
+    pub frames: Option<usize>,
+    pub easing: Easing,
+}
+
+/// `frames` frames spread over how much `snapshots` change, as paced by `easing`. Frames between
+/// two snapshots are interpolated between them.
+pub fn resample(snapshots: &[Locations], frames: usize, easing: Easing) -> Vec<Locations> {
+    if snapshots.len() < 2 || frames < 2 {
+        // A single frame shows the final shape, not the random start.
+        return snapshots.iter().rev().take(frames).cloned().collect();
+    }
+
+    // Mean distance moved by a vertex from the first snapshot to each snapshot.
+    let mut progress = vec![0.0];
+    for pair in snapshots.windows(2) {
+        let moved = pair[0]
+            .iter()
+            .zip(pair[1].iter())
+            .map(|(a, b)| nalgebra::distance(a, b))
+            .sum::<f64>()
+            / pair[0].len() as f64;
+        progress.push(progress.last().unwrap() + moved);
+    }
+    let total = *progress.last().unwrap();
+
+    (0..frames)
+        .map(|frame| {
+            let target = total * easing.apply(frame as f64 / (frames - 1) as f64);
+            // The snapshots just before and after the target.
+            let after = progress
+                .partition_point(|p| *p < target)
+                .clamp(1, snapshots.len() - 1);
+            let before = after - 1;
+            let span = progress[after] - progress[before];
+            let t = if span > 0.0 {
+                ((target - progress[before]) / span).clamp(0.0, 1.0)
+            } else {
+                0.0
+            };
+            snapshots[before]
+                .iter()
+                .zip(snapshots[after].iter())
+                .map(|(a, b)| a + t * (b - a))
+                .collect()
+        })
+        .collect()
+}
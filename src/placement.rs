@@ -0,0 +1,113 @@
+//! Sizing and positioning of the relaxed solid for printing.
+//!
+//! The relaxation leaves the solid at whatever scale balances the springs against the repulsion.
+//! STL has no units, but slicers read it as millimetres, so sizes given here are in millimetres.
+
+use nalgebra::{Point3, Similarity3, Translation3, UnitQuaternion, Vector3};
+
+use crate::solid::{Locations, Neighbors};
+use crate::triangulate::{face_normal, hull_triangles, merge_faces};
+
+#[derive(Clone, Copy, Debug)]
+pub enum Size {
+    /// Mean length of the edges of the graph.
+    EdgeLength(f64),
+    /// Largest distance from the centroid to a vertex.
+    Circumradius(f64),
+    /// Largest side of the axis aligned bounding box.
+    BoundingBox(f64),
+}
+
+pub struct PlacementParams {
+    pub size: Option<Size>,
+    /// Rotate the solid so this face (as numbered by `merge_faces`) points down, then move it up
+    /// so it rests on z=0.
+    pub rest_on_face: Option<usize>,
+}
+
+/// The transform that sizes and positions `locations` as asked for by `params`. The relaxation
+/// keeps the centroid at the origin, and so does this unless the solid is put down on a face.
+/// Fails if the solid has no face `params.rest_on_face`.
+pub fn placement(
+    locations: &Locations,
+    neighbors: &Neighbors,
+    params: &PlacementParams,
+) -> Result<Similarity3<f64>, String> {
+    let scaling = match params.size {
+        None => 1.0,
+        Some(Size::EdgeLength(target)) => {
+            let (total, count) = neighbors
+                .iter()
+                .enumerate()
+                .flat_map(|(a, vertex_neighbors)| vertex_neighbors.iter().map(move |&b| (a, b)))
+                .fold((0.0, 0), |(total, count), (a, b)| {
+                    (
+                        total + nalgebra::distance(&locations[a], &locations[b]),
+                        count + 1,
+                    )
+                });
+            target / (total / count as f64)
+        }
+        Some(Size::Circumradius(target)) => {
+            let centroid = centroid(locations);
+            let radius = locations
+                .iter()
+                .map(|p| nalgebra::distance(p, &centroid))
+                .fold(0.0, f64::max);
+            target / radius
+        }
+        Some(Size::BoundingBox(target)) => {
+            let (min, max) = bounds(locations.iter().copied());
+            target / (max - min).max()
+        }
+    };
+
+    let rotation = match params.rest_on_face {
+        None => UnitQuaternion::identity(),
+        Some(face_id) => {
+            let faces = merge_faces(&hull_triangles(locations), locations, neighbors);
+            let face = faces.get(face_id).ok_or_else(|| {
+                format!("no face {}, the solid has {} faces", face_id, faces.len())
+            })?;
+            UnitQuaternion::rotation_between(&face_normal(face, locations), &-Vector3::z())
+                .unwrap_or_else(|| {
+                    // Already pointing straight up.
+                    UnitQuaternion::from_axis_angle(&Vector3::x_axis(), std::f64::consts::PI)
+                })
+        }
+    };
+
+    let mut translation = Translation3::identity();
+    if params.rest_on_face.is_some() {
+        let rotated = locations.iter().map(|p| scaling * (rotation * p));
+        let (min, _) = bounds(rotated);
+        translation.z = -min.z;
+    }
+
+    Ok(Similarity3::from_parts(translation, rotation, scaling))
+}
+
+pub fn transform(locations: &mut Locations, transform: &Similarity3<f64>) {
+    for p in locations.iter_mut() {
+        *p = transform * *p;
+    }
+}
+
+/// Move `locations` up or down so the lowest point is on z=0.
+pub fn drop_to_floor(locations: &mut Locations) {
+    let (min, _) = bounds(locations.iter().copied());
+    for p in locations.iter_mut() {
+        p.z -= min.z;
+    }
+}
+
+fn centroid(locations: &Locations) -> Point3<f64> {
+    Point3::from(locations.iter().map(|p| p.coords).sum::<Vector3<f64>>() / locations.len() as f64)
+}
+
+fn bounds(points: impl Iterator<Item = Point3<f64>>) -> (Vector3<f64>, Vector3<f64>) {
+    points.fold(
+        (Vector3::repeat(f64::MAX), Vector3::repeat(f64::MIN)),
+        |(min, max), p| (min.inf(&p.coords), max.sup(&p.coords)),
+    )
+}
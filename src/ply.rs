@@ -0,0 +1,241 @@
+//! PLY export of a relaxed solid.
+//!
+//! Unlike STL, PLY can carry per-vertex and per-face properties, so the residual forces left by
+//! the relaxation can be inspected in e.g. MeshLab.
+
+use std::fs::File;
+use std::io::{BufWriter, Write};
+use std::path::Path;
+
+use clap::ValueEnum;
+use strum::Display;
+
+use crate::relax::Forces;
+use crate::solid::{Faces, Locations, Neighbors};
+use crate::triangulate::face_normal;
+
+#[derive(Clone, Copy, Debug, Display, ValueEnum)]
+#[strum(serialize_all = "kebab-case")]
+pub enum PlyFormat {
+    Ascii,
+    /// Little endian binary.
+    Binary,
+}
+
+#[derive(Clone, Copy, Debug, Display, PartialEq, ValueEnum)]
+#[strum(serialize_all = "kebab-case")]
+pub enum PlyAttribute {
+    /// Number of edges at each vertex.
+    Degree,
+    /// Magnitude of the net force on each vertex in the final state.
+    Force,
+    /// Per-vertex colour, blue for the smallest force and red for the largest.
+    Color,
+    /// Outward unit normal of each face.
+    Normal,
+    /// Number of vertices of each face.
+    Valence,
+}
+
+pub struct PlyParams {
+    pub format: PlyFormat,
+    pub attributes: Vec<PlyAttribute>,
+}
+
+enum Value {
+    Float(f64),
+    Int(i32),
+    UChar(u8),
+}
+
+fn write_value<W: Write>(w: &mut W, format: PlyFormat, value: Value) -> std::io::Result<()> {
+    match (format, value) {
+        (PlyFormat::Ascii, Value::Float(x)) => write!(w, " {:.6}", x),
+        (PlyFormat::Ascii, Value::Int(x)) => write!(w, " {}", x),
+        (PlyFormat::Ascii, Value::UChar(x)) => write!(w, " {}", x),
+        (PlyFormat::Binary, Value::Float(x)) => w.write_all(&(x as f32).to_le_bytes()),
+        (PlyFormat::Binary, Value::Int(x)) => w.write_all(&x.to_le_bytes()),
+        (PlyFormat::Binary, Value::UChar(x)) => w.write_all(&[x]),
+    }
+}
+
+fn end_element<W: Write>(w: &mut W, format: PlyFormat) -> std::io::Result<()> {
+    match format {
+        PlyFormat::Ascii => writeln!(w),
+        PlyFormat::Binary => Ok(()),
+    }
+}
+
+// Blue -> green -> red ramp for t in [0, 1].
+fn heat(t: f64) -> [u8; 3] {
+    let t = t.clamp(0.0, 1.0);
+    let r = (2.0 * t - 1.0).max(0.0);
+    let b = (1.0 - 2.0 * t).max(0.0);
+    let g = 1.0 - r - b;
+    [r, g, b].map(|c| (c * 255.0).round() as u8)
+}
+
+pub fn to_ply<P: AsRef<Path>>(
+    path: P,
+    locations: &Locations,
+    faces: &Faces,
+    neighbors: &Neighbors,
+    forces: &Forces,
+    params: &PlyParams,
+) -> std::io::Result<()> {
+    let file = File::create(path)?;
+    let mut w = BufWriter::new(file);
+
+    let format = params.format;
+    let has = |attribute| params.attributes.contains(&attribute);
+
+    let force_magnitudes: Vec<f64> = forces.iter().map(|f| f.norm()).collect();
+    let max_force = force_magnitudes.iter().copied().fold(0.0, f64::max);
+
+    writeln!(w, "ply")?;
+    match format {
+        PlyFormat::Ascii => writeln!(w, "format ascii 1.0")?,
+        PlyFormat::Binary => writeln!(w, "format binary_little_endian 1.0")?,
+    }
+    writeln!(w, "comment generated by solids")?;
+
+    writeln!(w, "element vertex {}", locations.len())?;
+    writeln!(w, "property float x")?;
+    writeln!(w, "property float y")?;
+    writeln!(w, "property float z")?;
+    if has(PlyAttribute::Degree) {
+        writeln!(w, "property int degree")?;
+    }
+    if has(PlyAttribute::Force) {
+        writeln!(w, "property float force")?;
+    }
+    if has(PlyAttribute::Color) {
+        writeln!(w, "property uchar red")?;
+        writeln!(w, "property uchar green")?;
+        writeln!(w, "property uchar blue")?;
+    }
+
+    writeln!(w, "element face {}", faces.len())?;
+    writeln!(w, "property list uchar int vertex_indices")?;
+    if has(PlyAttribute::Normal) {
+        writeln!(w, "property float nx")?;
+        writeln!(w, "property float ny")?;
+        writeln!(w, "property float nz")?;
+    }
+    if has(PlyAttribute::Valence) {
+        writeln!(w, "property uchar valence")?;
+    }
+    writeln!(w, "end_header")?;
+
+    for (vertex, p) in locations.iter().enumerate() {
+        for c in [p.x, p.y, p.z] {
+            write_value(&mut w, format, Value::Float(c))?;
+        }
+        if has(PlyAttribute::Degree) {
+            write_value(&mut w, format, Value::Int(neighbors[vertex].len() as i32))?;
+        }
+        if has(PlyAttribute::Force) {
+            write_value(&mut w, format, Value::Float(force_magnitudes[vertex]))?;
+        }
+        if has(PlyAttribute::Color) {
+            let t = if max_force > 0.0 {
+                force_magnitudes[vertex] / max_force
+            } else {
+                0.0
+            };
+            for c in heat(t) {
+                write_value(&mut w, format, Value::UChar(c))?;
+            }
+        }
+        end_element(&mut w, format)?;
+    }
+
+    for face in faces {
+        write_value(&mut w, format, Value::UChar(face.len() as u8))?;
+        for id in face {
+            write_value(&mut w, format, Value::Int(*id as i32))?;
+        }
+        if has(PlyAttribute::Normal) {
+            let n = face_normal(face, locations);
+            for c in [n.x, n.y, n.z] {
+                write_value(&mut w, format, Value::Float(c))?;
+            }
+        }
+        if has(PlyAttribute::Valence) {
+            write_value(&mut w, format, Value::UChar(face.len() as u8))?;
+        }
+        end_element(&mut w, format)?;
+    }
+
+    w.flush()?;
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use nalgebra::{Point3, Vector3};
+
+    use super::*;
+
+    #[test]
+    fn binary_ply_matches_its_header() {
+        let locations: Locations = vec![
+            Point3::new(1.0, 1.0, 1.0),
+            Point3::new(1.0, -1.0, -1.0),
+            Point3::new(-1.0, 1.0, -1.0),
+            Point3::new(-1.0, -1.0, 1.0),
+        ];
+        let faces: Faces = vec![vec![0, 1, 2], vec![0, 3, 1], vec![0, 2, 3], vec![1, 3, 2]];
+        let neighbors: Neighbors = (0..4)
+            .map(|a| (0..4).filter(|b| *b != a).collect())
+            .collect();
+        let forces: Forces = (0..4).map(|k| Vector3::new(k as f64, 0.0, 0.0)).collect();
+        let path = std::env::temp_dir().join(format!("solids-test-{}.ply", std::process::id()));
+        to_ply(
+            &path,
+            &locations,
+            &faces,
+            &neighbors,
+            &forces,
+            &PlyParams {
+                format: PlyFormat::Binary,
+                attributes: PlyAttribute::value_variants().to_vec(),
+            },
+        )
+        .unwrap();
+        let ply = std::fs::read(&path).unwrap();
+        std::fs::remove_file(&path).unwrap();
+
+        let end = b"end_header\n";
+        let body = ply.windows(end.len()).position(|w| w == end).unwrap() + end.len();
+        let header = std::str::from_utf8(&ply[..body]).unwrap();
+        assert!(header.starts_with("ply\nformat binary_little_endian 1.0\n"));
+        assert!(header.contains("element vertex 4\n"));
+        assert!(header.contains("element face 4\n"));
+
+        // Vertices: x, y, z, degree and force, 4 bytes each, then 3 colour bytes. Faces: the count
+        // byte, 3 indices, the normal and the valence byte.
+        let vertex_length = 4 * 4 + 4 + 3;
+        let face_length = 1 + 3 * 4 + 3 * 4 + 1;
+        assert_eq!(ply.len() - body, 4 * vertex_length + 4 * face_length);
+
+        let f32_at =
+            |offset: usize| f32::from_le_bytes(ply[offset..offset + 4].try_into().unwrap());
+        let i32_at =
+            |offset: usize| i32::from_le_bytes(ply[offset..offset + 4].try_into().unwrap());
+        let vertex = body + vertex_length;
+        assert_eq!(
+            [f32_at(vertex), f32_at(vertex + 4), f32_at(vertex + 8)],
+            [1.0, -1.0, -1.0]
+        );
+        assert_eq!(i32_at(vertex + 12), 3);
+        assert_eq!(f32_at(vertex + 16), 1.0);
+        let face = body + 4 * vertex_length + face_length;
+        assert_eq!(ply[face], 3);
+        assert_eq!(
+            [i32_at(face + 1), i32_at(face + 5), i32_at(face + 9)],
+            [0, 3, 1]
+        );
+        assert_eq!(ply[face + face_length - 1], 3);
+    }
+}
@@ -0,0 +1,95 @@
+//! A live view of a run in the terminal, without any GUI.
+//!
+//! Each character is two pixels, one above the other: the upper half block "▀" in the color of
+//! the top pixel on a background of the color of the bottom one, from the 24 greys of the 256
+//! color ANSI palette. New frames are drawn over the last one.
+//!
+//! Moving back up over the last frame only works if none of its lines wrapped, so the preview is
+//! no wider than `$COLUMNS` when the shell exports it, and line wrapping is turned off while it
+//! is drawn, which cuts off whatever doesn't fit instead.
+
+use std::io::Write;
+
+// Width of the preview in characters, if the terminal is wide enough.
+const COLUMNS: usize = 64;
+
+#[derive(Default)]
+pub struct Preview {
+    /// Index of the frame on screen.
+    shown: Option<usize>,
+    /// Lines the frame on screen takes up.
+    lines: usize,
+}
+
+impl Preview {
+    /// Draw `image`, frame `index`, on stderr in place of the last frame, unless a later frame is
+    /// already on screen.
+    pub fn show(&mut self, index: usize, image: &ndarray::Array3<u8>) {
+        if self.shown.is_some_and(|shown| shown >= index) {
+            return;
+        }
+        let terminal_columns = std::env::var("COLUMNS")
+            .ok()
+            .and_then(|columns| columns.parse::<usize>().ok())
+            .filter(|columns| *columns > 0);
+        let text = half_blocks(image, terminal_columns.map_or(COLUMNS, |c| c.min(COLUMNS)));
+
+        let mut stderr = std::io::stderr().lock();
+        // No line wrapping.
+        write!(stderr, "\x1b[?7l").unwrap();
+        if self.lines > 0 {
+            // Back to the top of the last frame.
+            write!(stderr, "\x1b[{}A", self.lines).unwrap();
+        }
+        writeln!(stderr, "{}frame {}\x1b[K", text, index).unwrap();
+        write!(stderr, "\x1b[?7h").unwrap();
+        stderr.flush().unwrap();
+
+        self.lines = text.lines().count() + 1;
+        self.shown = Some(index);
+    }
+}
+
+// `image` scaled down to `columns` characters wide, as lines of half blocks.
+fn half_blocks(image: &ndarray::Array3<u8>, columns: usize) -> String {
+    let (h, w, _) = image.dim();
+    let columns = columns.min(w);
+    // Half blocks are about square, so the rows of them that keep the aspect ratio, rounded down
+    // to whole characters.
+    let rows = (columns * h / w).max(2) & !1;
+
+    // The pixels of a `size` long side that cell `k` of `cells` covers, at least one.
+    let span = |k: usize, cells: usize, size: usize| {
+        let start = k * size / cells;
+        start..((k + 1) * size / cells).max(start + 1)
+    };
+    // Mean luma of the pixels of `image` in cell (x, y) of a `columns` by `rows` grid.
+    let grey = |x: usize, y: usize| {
+        let (xs, ys) = (span(x, columns, w), span(y, rows, h));
+        let count = xs.len() * ys.len();
+        let mut sum = 0.0;
+        for py in ys {
+            for px in xs.clone() {
+                sum += 0.299 * image[(py, px, 0)] as f64
+                    + 0.587 * image[(py, px, 1)] as f64
+                    + 0.114 * image[(py, px, 2)] as f64;
+            }
+        }
+        sum / count as f64
+    };
+    // The grey ramp runs from code 232, almost black, to 255, almost white.
+    let code = |grey: f64| 232 + (grey / 255.0 * 23.0).round() as u8;
+
+    let mut text = String::new();
+    for y in (0..rows).step_by(2) {
+        for x in 0..columns {
+            text.push_str(&format!(
+                "\x1b[38;5;{}m\x1b[48;5;{}m▀",
+                code(grey(x, y)),
+                code(grey(x, y + 1))
+            ));
+        }
+        text.push_str("\x1b[0m\n");
+    }
+    text
+}
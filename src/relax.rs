@@ -5,10 +5,9 @@ use rand::prelude::*;
 
 use crate::solid::{Locations, Neighbors};
 
-type Forces = Vec<Vector3<f64>>;
+pub type Forces = Vec<Vector3<f64>>;
 
-fn random_point(sphere_size: f64) -> Point3<f64> {
-    let mut rng = rand::rng();
+fn random_point(rng: &mut impl Rng, sphere_size: f64) -> Point3<f64> {
     let (x, y, z): (f64, f64, f64) = rng.random();
     sphere_size * Point3::new(x, y, z)
 }
@@ -20,9 +19,32 @@ pub struct RelaxParams {
     pub step_size: f64,
     pub total_movement_thresh: f64,
     pub snapshot_period: u32,
+    /// Send a snapshot whenever the vertices have moved this far on average since the last one,
+    /// instead of every `snapshot_period` steps.
+    pub snapshot_movement: Option<f64>,
+    /// Seed of the random starting points, for runs that can be repeated.
+    pub seed: Option<u64>,
     pub locations_tx: Option<Sender<Locations>>,
 }
 
+/// The constants every output relaxes with: unit springs, a snapshot every 10 000 steps if
+/// anyone listens, and a random start.
+impl Default for RelaxParams {
+    fn default() -> Self {
+        RelaxParams {
+            spring_constant: 1.0,
+            repulsion_constant: 0.1,
+            natural_length: 1.0,
+            step_size: 1e-4,
+            total_movement_thresh: 1e-7,
+            snapshot_period: 10_000,
+            snapshot_movement: None,
+            seed: None,
+            locations_tx: None,
+        }
+    }
+}
+
 // Relax the locations of the neighbors by assuming each edge is a spring with damper.
 pub fn relax(neighbors: &Neighbors, relax_params: RelaxParams) -> Locations {
     let RelaxParams {
@@ -32,54 +54,31 @@ pub fn relax(neighbors: &Neighbors, relax_params: RelaxParams) -> Locations {
         total_movement_thresh,
         repulsion_constant,
         snapshot_period,
+        snapshot_movement,
+        seed,
         locations_tx,
     } = relax_params;
 
+    let mut rng = match seed {
+        Some(seed) => StdRng::seed_from_u64(seed),
+        None => StdRng::from_rng(&mut rand::rng()),
+    };
     let mut locations: Locations = neighbors
         .iter()
-        .map(|_| random_point(1.0))
-        .collect();
-    let mut forces: Forces = neighbors
-        .iter()
-        .map(|_| Vector3::new(0.0, 0.0, 0.0))
+        .map(|_| random_point(&mut rng, 1.0))
         .collect();
 
     let mut step = 0;
+    // Mean distance travelled by a vertex since the last snapshot.
+    let mut moved = 0.0;
     loop {
-        // Reset forces
-        for f in forces.iter_mut() {
-            *f = Vector3::new(0.0, 0.0, 0.0);
-        }
-
-        // Calculate the net force on each vertex.
-        for (vertex, vertex_neighbors) in neighbors.iter().enumerate() {
-            let this_vertex_location = &locations[vertex];
-
-            for &neighbor in vertex_neighbors {
-                let neighbor_location = &locations[neighbor];
-                let distance = nalgebra::distance(neighbor_location, this_vertex_location);
-
-                // Spring.
-                let spring_force_mag = 0.5 * spring_constant * (distance - natural_length);
-                forces[vertex] +=
-                    spring_force_mag * (neighbor_location - this_vertex_location).normalize();
-            }
-
-            // Repulsion.
-            for other_vertex in 0..neighbors.len() {
-                if other_vertex == vertex {
-                    continue;
-                }
-
-                let neighbor_location = &locations[other_vertex];
-                let distance = nalgebra::distance(neighbor_location, this_vertex_location);
-
-                let repulsion_force_mag = -repulsion_constant / (distance * distance);
-
-                forces[vertex] +=
-                    repulsion_force_mag * (neighbor_location - this_vertex_location).normalize();
-            }
-        }
+        let forces = net_forces(
+            neighbors,
+            &locations,
+            spring_constant,
+            repulsion_constant,
+            natural_length,
+        );
 
         // Update states.
         let mut total_movement = 0.0;
@@ -99,10 +98,16 @@ pub fn relax(neighbors: &Neighbors, relax_params: RelaxParams) -> Locations {
             p.coords -= centroid;
         }
 
-        if let Some(ref ch) = locations_tx {
-            if step % snapshot_period == 0 {
-                let _ = ch.send(locations.clone());
-            }
+        moved += total_movement / neighbors.len() as f64;
+        let snapshot_due = match snapshot_movement {
+            Some(threshold) => step == 0 || moved >= threshold,
+            None => step % snapshot_period == 0,
+        };
+        if let Some(ref ch) = locations_tx
+            && snapshot_due
+        {
+            let _ = ch.send(locations.clone());
+            moved = 0.0;
         }
 
         step += 1;
@@ -117,3 +122,48 @@ pub fn relax(neighbors: &Neighbors, relax_params: RelaxParams) -> Locations {
 
     locations
 }
+
+/// Net force on each vertex: a spring along every edge plus inverse-square repulsion from every
+/// other vertex.
+pub fn net_forces(
+    neighbors: &Neighbors,
+    locations: &Locations,
+    spring_constant: f64,
+    repulsion_constant: f64,
+    natural_length: f64,
+) -> Forces {
+    let mut forces: Forces = neighbors
+        .iter()
+        .map(|_| Vector3::new(0.0, 0.0, 0.0))
+        .collect();
+
+    for (vertex, vertex_neighbors) in neighbors.iter().enumerate() {
+        let this_vertex_location = &locations[vertex];
+
+        for &neighbor in vertex_neighbors {
+            let neighbor_location = &locations[neighbor];
+            let distance = nalgebra::distance(neighbor_location, this_vertex_location);
+
+            // Spring.
+            let spring_force_mag = 0.5 * spring_constant * (distance - natural_length);
+            forces[vertex] +=
+                spring_force_mag * (neighbor_location - this_vertex_location).normalize();
+        }
+
+        // Repulsion.
+        for (other_vertex, neighbor_location) in locations.iter().enumerate() {
+            if other_vertex == vertex {
+                continue;
+            }
+
+            let distance = nalgebra::distance(neighbor_location, this_vertex_location);
+
+            let repulsion_force_mag = -repulsion_constant / (distance * distance);
+
+            forces[vertex] +=
+                repulsion_force_mag * (neighbor_location - this_vertex_location).normalize();
+        }
+    }
+
+    forces
+}
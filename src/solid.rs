@@ -1,4 +1,9 @@
+use std::sync::OnceLock;
+
+use crate::bvh::Bvh;
 use crate::view::Draw;
+use crate::view::color::Color;
+use crate::view::light::Lighting;
 
 const EPS: f64 = 1e-6;
 
@@ -6,76 +11,243 @@ pub type VertexId = usize;
 pub type Locations = Vec<nalgebra::Point3<f64>>;
 pub type Neighbors = Vec<Vec<VertexId>>;
 pub type Triangles = Vec<[VertexId; 3]>;
+/// Polygonal faces, each a loop of vertices ordered counter-clockwise seen from outside.
+pub type Faces = Vec<Vec<VertexId>>;
 
 pub struct Solid {
     pub locations: Locations,
     pub triangles: Triangles,
+    /// Color of each triangle, white unless set.
+    pub colors: Vec<Color>,
+    /// Built on the first ray cast, so solids that are only written out never pay for it.
+    bvh: OnceLock<Bvh>,
 }
 
-impl Draw for Solid {
+/// Where a ray meets a solid.
+#[derive(Clone, Copy, Debug)]
+pub struct Hit {
+    /// Distance along the ray.
+    pub distance: f64,
+    /// Outward unit normal of the triangle hit.
+    pub normal: nalgebra::Vector3<f64>,
+    /// Index of the triangle hit.
+    pub triangle: usize,
+}
+
+impl Solid {
+    pub fn new(locations: Locations, triangles: Triangles) -> Self {
+        Solid {
+            locations,
+            colors: vec![Color::repeat(1.0); triangles.len()],
+            triangles,
+            bvh: OnceLock::new(),
+        }
+    }
+
+    /// The nearest triangle the ray hits.
+    pub fn closest_hit(
+        &self,
+        ray_source: nalgebra::Point3<f64>,
+        ray_direction: nalgebra::UnitVector3<f64>,
+    ) -> Option<Hit> {
+        let bvh = self
+            .bvh
+            .get_or_init(|| Bvh::new(&self.locations, &self.triangles));
+        let mut closest: Option<Hit> = None;
+        bvh.traverse(ray_source, ray_direction, |t| {
+            let (distance, normal) =
+                self.intersect_triangle(&self.triangles[t], ray_source, ray_direction)?;
+            if closest.is_none_or(|hit| hit.distance > distance) {
+                closest = Some(Hit {
+                    distance,
+                    normal,
+                    triangle: t,
+                });
+            }
+            Some(distance)
+        });
+        closest
+    }
+
+    // Color of the surface seen along the ray at `hit`.
+    fn shade_hit(
+        &self,
+        hit: Option<Hit>,
+        ray_source: nalgebra::Point3<f64>,
+        ray_direction: nalgebra::UnitVector3<f64>,
+        lighting: &Lighting,
+    ) -> Color {
+        hit.map_or(Color::zeros(), |hit| {
+            lighting.shade(
+                ray_source + hit.distance * *ray_direction,
+                &hit.normal,
+                &ray_direction,
+                &self.colors[hit.triangle],
+            )
+        })
+    }
+
+    // Distance along the ray to the triangle and its normal, if the ray hits it.
     // The ray is defined by P(r) = ray_source + r * ray_direction.
     // Algorithm from https://web.archive.org/web/20210330124410/http://geomalgorithms.com/a06-_intersect-2.html
-    fn intersect(
+    fn intersect_triangle(
         &self,
+        [v0_id, v1_id, v2_id]: &[VertexId; 3],
         ray_source: nalgebra::Point3<f64>,
         ray_direction: nalgebra::UnitVector3<f64>,
-    ) -> f64 {
-        let mut min_distance = f64::MAX;
-        let mut shade = 0.0;
-        for [v0_id, v1_id, v2_id] in self.triangles.iter() {
-            // Locations of the verticies of the triangle.
-            let (v0, v1, v2) = (
-                self.locations[*v0_id],
-                self.locations[*v1_id],
-                self.locations[*v2_id],
-            );
-
-            // Two of the edges of the triangle.
-            let u = v1 - v0;
-            let v = v2 - v0;
-
-            // First, determine if the ray intersects with the plane of the triangle.
-            let triangle_normal = u.cross(&v).normalize();
-
-            // Triangle is parallel or in-plane with the ray.
-            if triangle_normal.dot(&ray_direction).abs() < EPS {
-                continue;
-            }
+    ) -> Option<(f64, nalgebra::Vector3<f64>)> {
+        // Locations of the verticies of the triangle.
+        let (v0, v1, v2) = (
+            self.locations[*v0_id],
+            self.locations[*v1_id],
+            self.locations[*v2_id],
+        );
 
-            let ray_triangle_plane_intersection =
-                triangle_normal.dot(&(v0 - ray_source)) / triangle_normal.dot(&ray_direction);
+        // Two of the edges of the triangle.
+        let u = v1 - v0;
+        let v = v2 - v0;
 
-            // Triangle is behind camera.
-            if ray_triangle_plane_intersection < 0.0 {
-                continue;
-            }
+        // First, determine if the ray intersects with the plane of the triangle.
+        let triangle_normal = u.cross(&v).normalize();
 
-            // Now, determine whether the intersection with the plane is in the triangle.
-            let triangle_plane_intersection =
-                ray_source + ray_triangle_plane_intersection * *ray_direction;
-            let w = triangle_plane_intersection - v0;
+        // Triangle is parallel or in-plane with the ray.
+        if triangle_normal.dot(&ray_direction).abs() < EPS {
+            return None;
+        }
 
-            let n1 = u.dot(&v) * w.dot(&v) - (v.dot(&v)) * w.dot(&u);
-            let n2 = u.dot(&v) * w.dot(&u) - (u.dot(&u)) * w.dot(&v);
-            let denom = u.dot(&v).powf(2.0) - (u.dot(&u)) * (v.dot(&v));
-            if denom.abs() < EPS {
-                continue;
-            }
+        let ray_triangle_plane_intersection =
+            triangle_normal.dot(&(v0 - ray_source)) / triangle_normal.dot(&ray_direction);
 
-            let (s, t) = (n1 / denom, n2 / denom);
+        // Triangle is behind camera.
+        if ray_triangle_plane_intersection < 0.0 {
+            return None;
+        }
 
-            // Intersection is within the triangle.
-            if 0.0 <= s
-                && 0.0 <= t
-                && (0.0 <= s + t && s + t <= 1.0)
-                && min_distance > ray_triangle_plane_intersection
+        // Now, determine whether the intersection with the plane is in the triangle.
+        let triangle_plane_intersection =
+            ray_source + ray_triangle_plane_intersection * *ray_direction;
+        let w = triangle_plane_intersection - v0;
+
+        let n1 = u.dot(&v) * w.dot(&v) - (v.dot(&v)) * w.dot(&u);
+        let n2 = u.dot(&v) * w.dot(&u) - (u.dot(&u)) * w.dot(&v);
+        let denom = u.dot(&v).powf(2.0) - (u.dot(&u)) * (v.dot(&v));
+        if denom.abs() < EPS {
+            return None;
+        }
+
+        let (s, t) = (n1 / denom, n2 / denom);
+
+        // Intersection is within the triangle.
+        if 0.0 <= s && 0.0 <= t && (0.0 <= s + t && s + t <= 1.0) {
+            Some((ray_triangle_plane_intersection, triangle_normal))
+        } else {
+            None
+        }
+    }
+}
+
+/// Draws a solid by testing every triangle instead of using the bounding volume hierarchy.
+pub struct BruteForce<'a>(pub &'a Solid);
+
+impl BruteForce<'_> {
+    /// The nearest triangle the ray hits, as `Solid::closest_hit` finds it.
+    pub fn closest_hit(
+        &self,
+        ray_source: nalgebra::Point3<f64>,
+        ray_direction: nalgebra::UnitVector3<f64>,
+    ) -> Option<Hit> {
+        let mut closest: Option<Hit> = None;
+        for (t, triangle) in self.0.triangles.iter().enumerate() {
+            if let Some((distance, normal)) =
+                self.0
+                    .intersect_triangle(triangle, ray_source, ray_direction)
+                && closest.is_none_or(|hit| hit.distance > distance)
             {
-                min_distance = ray_triangle_plane_intersection;
-                shade = triangle_normal
-                    .dot(&(-ray_direction.normalize()))
-                    .clamp(0.0, 1.0);
+                closest = Some(Hit {
+                    distance,
+                    normal,
+                    triangle: t,
+                });
+            }
+        }
+        closest
+    }
+}
+
+impl Draw for BruteForce<'_> {
+    fn intersect(
+        &self,
+        ray_source: nalgebra::Point3<f64>,
+        ray_direction: nalgebra::UnitVector3<f64>,
+        lighting: &Lighting,
+    ) -> Color {
+        let hit = self.closest_hit(ray_source, ray_direction);
+        self.0.shade_hit(hit, ray_source, ray_direction, lighting)
+    }
+}
+
+impl Draw for Solid {
+    fn intersect(
+        &self,
+        ray_source: nalgebra::Point3<f64>,
+        ray_direction: nalgebra::UnitVector3<f64>,
+        lighting: &Lighting,
+    ) -> Color {
+        let hit = self.closest_hit(ray_source, ray_direction);
+        self.shade_hit(hit, ray_source, ray_direction, lighting)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use rand::prelude::*;
+
+    use super::*;
+    use crate::triangulate::hull_triangles;
+
+    // Hull of `n` random points on the unit sphere.
+    fn random_solid(rng: &mut StdRng, n: usize) -> Solid {
+        let locations: Locations = (0..n)
+            .map(|_| {
+                let (x, y, z): (f64, f64, f64) = rng.random();
+                let p = nalgebra::Vector3::new(x, y, z) - nalgebra::Vector3::repeat(0.5);
+                nalgebra::Point3::from(p.normalize())
+            })
+            .collect();
+        let triangles = hull_triangles(&locations);
+        Solid::new(locations, triangles)
+    }
+
+    #[test]
+    fn bvh_finds_the_same_hits_as_brute_force() {
+        let mut rng = StdRng::seed_from_u64(1);
+        for n in [4, 12, 40, 100] {
+            let solid = random_solid(&mut rng, n);
+            for _ in 0..500 {
+                // Rays from outside towards a point near the solid, some of them missing it.
+                let (a, b): ([f64; 3], [f64; 3]) = (rng.random(), rng.random());
+                let source = nalgebra::Point3::from(
+                    (nalgebra::Vector3::from(a) - nalgebra::Vector3::repeat(0.5)).normalize() * 3.0,
+                );
+                let target = nalgebra::Point3::from(
+                    (nalgebra::Vector3::from(b) - nalgebra::Vector3::repeat(0.5)) * 2.4,
+                );
+                let direction = nalgebra::Unit::new_normalize(target - source);
+
+                let bvh = solid.closest_hit(source, direction);
+                let brute_force = BruteForce(&solid).closest_hit(source, direction);
+                match (bvh, brute_force) {
+                    (None, None) => {}
+                    (Some(bvh), Some(brute_force)) => {
+                        // Rays through an edge may hit either triangle at the same distance.
+                        assert!((bvh.distance - brute_force.distance).abs() < 1e-9);
+                    }
+                    (bvh, brute_force) => panic!(
+                        "{} points: bvh hit {:?}, brute force hit {:?}",
+                        n, bvh, brute_force
+                    ),
+                }
             }
         }
-        shade
     }
 }
@@ -1,8 +1,9 @@
+use std::collections::HashMap;
 use std::fs::File;
 use std::io::{BufWriter, Write};
 use std::path::Path;
 
-use crate::solid::{Locations, VertexId};
+use crate::solid::{Faces, Locations, Neighbors, Triangles, VertexId};
 
 /// Slow hull algorithm.
 pub fn hull_triangles(locations: &Locations) -> Vec<[VertexId; 3]> {
@@ -80,6 +81,405 @@ pub fn hull_triangles(locations: &Locations) -> Vec<[VertexId; 3]> {
     tris
 }
 
+/// Merge hull triangles that share an edge which is not an edge of the graph. The faces of the
+/// solid then come out as the polygons bounded by graph edges (e.g. the pentagons of the
+/// dodecahedron), even where the relaxation leaves them slightly non-planar.
+pub fn merge_faces(triangles: &Triangles, locations: &Locations, neighbors: &Neighbors) -> Faces {
+    // Union-find over triangles.
+    let mut parent: Vec<usize> = (0..triangles.len()).collect();
+    fn root(parent: &mut [usize], mut i: usize) -> usize {
+        while parent[i] != i {
+            parent[i] = parent[parent[i]];
+            i = parent[i];
+        }
+        i
+    }
+
+    let mut edge_owner = HashMap::<(VertexId, VertexId), usize>::new();
+    for (t, tri) in triangles.iter().enumerate() {
+        for k in 0..3 {
+            let (a, b) = (tri[k], tri[(k + 1) % 3]);
+            if neighbors[a].contains(&b) {
+                continue; // a real edge separates two faces
+            }
+            let key = (a.min(b), a.max(b));
+            match edge_owner.get(&key) {
+                Some(&other) => {
+                    let (ra, rb) = (root(&mut parent, t), root(&mut parent, other));
+                    parent[ra] = rb;
+                }
+                None => {
+                    edge_owner.insert(key, t);
+                }
+            }
+        }
+    }
+
+    let mut groups = HashMap::<usize, Vec<usize>>::new();
+    for t in 0..triangles.len() {
+        groups.entry(root(&mut parent, t)).or_default().push(t);
+    }
+    let mut groups: Vec<Vec<usize>> = groups.into_values().collect();
+    groups.sort_by_key(|group| group[0]);
+
+    groups
+        .iter()
+        .map(|group| {
+            let mut face = Vec::<VertexId>::new();
+            let mut nrm = nalgebra::Vector3::zeros();
+            for &t in group {
+                let [p0, p1, p2] = triangles[t].map(|id| locations[id]);
+                nrm += (p1 - p0).cross(&(p2 - p0));
+                for id in triangles[t] {
+                    if !face.contains(&id) {
+                        face.push(id);
+                    }
+                }
+            }
+
+            sort_around_normal(&mut face, &nrm, locations);
+            face
+        })
+        .collect()
+}
+
+/// Merge hull triangles lying in a common plane into convex polygonal faces. `hull_triangles`
+/// emits every triangle of a coplanar set of points, so e.g. the four overlapping triangles on a
+/// square become a single quad.
+pub fn coplanar_faces(triangles: &Triangles, locations: &Locations) -> Faces {
+    let eps = 1e-6;
+
+    let mut planes = Vec::<(nalgebra::Vector3<f64>, f64)>::new();
+    let mut faces = Faces::new();
+
+    for tri in triangles {
+        let [p0, p1, p2] = tri.map(|id| locations[id]);
+        let nrm = (p1 - p0).cross(&(p2 - p0));
+        if nrm.norm() < eps {
+            continue; // degenerate
+        }
+        let nrm = nrm.normalize();
+
+        let same_plane = planes.iter().position(|(n, d)| {
+            n.dot(&nrm) > 1.0 - eps
+                && [p0, p1, p2]
+                    .iter()
+                    .all(|p| (n.dot(&p.coords) - d).abs() < eps)
+        });
+        match same_plane {
+            Some(face_id) => {
+                for id in tri {
+                    if !faces[face_id].contains(id) {
+                        faces[face_id].push(*id);
+                    }
+                }
+            }
+            None => {
+                planes.push((nrm, nrm.dot(&p0.coords)));
+                faces.push(tri.to_vec());
+            }
+        }
+    }
+
+    for (face, (nrm, _)) in faces.iter_mut().zip(planes.iter()) {
+        sort_around_normal(face, nrm, locations);
+    }
+
+    faces
+}
+
+// Order the vertices of a face counter-clockwise around the normal.
+fn sort_around_normal(face: &mut [VertexId], nrm: &nalgebra::Vector3<f64>, locations: &Locations) {
+    let centroid = face
+        .iter()
+        .map(|id| locations[*id].coords)
+        .sum::<nalgebra::Vector3<f64>>()
+        / face.len() as f64;
+    let u = (locations[face[0]].coords - centroid).normalize();
+    let v = nrm.normalize().cross(&u);
+    let angle = |id: &VertexId| {
+        let w = locations[*id].coords - centroid;
+        w.dot(&v).atan2(w.dot(&u))
+    };
+    face.sort_by(|a, b| angle(a).total_cmp(&angle(b)));
+}
+
+/// Outward unit normal of a face, by Newell's method since faces need not be exactly planar.
+pub fn face_normal(face: &[VertexId], locations: &Locations) -> nalgebra::Vector3<f64> {
+    let mut n = nalgebra::Vector3::zeros();
+    for (k, id) in face.iter().enumerate() {
+        let (a, b) = (locations[*id], locations[face[(k + 1) % face.len()]]);
+        n += a.coords.cross(&b.coords);
+    }
+    n.normalize()
+}
+
+/// Split convex faces back into triangles, as a fan around the first vertex of each face.
+pub fn fan_triangles(faces: &Faces) -> Triangles {
+    faces
+        .iter()
+        .flat_map(|face| (1..face.len() - 1).map(|k| [face[0], face[k], face[k + 1]]))
+        .collect()
+}
+
+/// Result of checking a triangle mesh before it is written out.
+pub struct MeshReport {
+    pub vertices: usize,
+    pub edges: usize,
+    pub triangles: usize,
+    pub expected_euler_characteristic: i64,
+    /// Edges used by a single triangle.
+    pub boundary_edges: usize,
+    /// Edges used by more than two triangles.
+    pub non_manifold_edges: usize,
+    /// Vertices whose triangles don't form a single fan.
+    pub non_manifold_vertices: usize,
+    /// Edges traversed in the same direction by two triangles.
+    pub inconsistent_edges: usize,
+    pub degenerate_triangles: usize,
+    /// Pairs of coplanar triangles whose interiors overlap.
+    pub overlapping_triangles: usize,
+    /// Positive when the triangles are wound counter-clockwise seen from outside.
+    pub signed_volume: f64,
+}
+
+impl MeshReport {
+    pub fn euler_characteristic(&self) -> i64 {
+        self.vertices as i64 - self.edges as i64 + self.triangles as i64
+    }
+
+    pub fn is_watertight(&self) -> bool {
+        self.boundary_edges == 0 && self.non_manifold_edges == 0
+    }
+
+    pub fn is_manifold(&self) -> bool {
+        self.non_manifold_edges == 0 && self.non_manifold_vertices == 0
+    }
+
+    pub fn is_consistently_wound(&self) -> bool {
+        self.inconsistent_edges == 0
+    }
+
+    /// Only meaningful for a watertight, consistently wound mesh.
+    pub fn has_outward_normals(&self) -> bool {
+        self.signed_volume > 0.0
+    }
+
+    pub fn is_valid(&self) -> bool {
+        self.is_watertight()
+            && self.is_manifold()
+            && self.is_consistently_wound()
+            && self.degenerate_triangles == 0
+            && self.overlapping_triangles == 0
+            && self.has_outward_normals()
+            && self.euler_characteristic() == self.expected_euler_characteristic
+    }
+}
+
+impl std::fmt::Display for MeshReport {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let yes_no = |ok: bool| if ok { "yes" } else { "NO" };
+        writeln!(
+            f,
+            "mesh: {} vertices, {} edges, {} triangles",
+            self.vertices, self.edges, self.triangles
+        )?;
+        writeln!(
+            f,
+            "  watertight:            {} ({} boundary edges, {} edges shared by more than two)",
+            yes_no(self.is_watertight()),
+            self.boundary_edges,
+            self.non_manifold_edges
+        )?;
+        writeln!(
+            f,
+            "  manifold:              {} ({} non-manifold edges, {} non-manifold vertices)",
+            yes_no(self.is_manifold()),
+            self.non_manifold_edges,
+            self.non_manifold_vertices
+        )?;
+        writeln!(
+            f,
+            "  consistent winding:    {} ({} edges traversed twice in one direction)",
+            yes_no(self.is_consistently_wound()),
+            self.inconsistent_edges
+        )?;
+        writeln!(f, "  degenerate triangles:  {}", self.degenerate_triangles)?;
+        writeln!(
+            f,
+            "  overlapping triangles: {} pairs",
+            self.overlapping_triangles
+        )?;
+        writeln!(
+            f,
+            "  outward normals:       {} (signed volume {:.6})",
+            yes_no(self.has_outward_normals()),
+            self.signed_volume
+        )?;
+        write!(
+            f,
+            "  euler characteristic:  {} ({}, expected {})",
+            yes_no(self.euler_characteristic() == self.expected_euler_characteristic),
+            self.euler_characteristic(),
+            self.expected_euler_characteristic
+        )
+    }
+}
+
+/// Check that the triangles form a closed, manifold, consistently and outward wound surface
+/// without degenerate or overlapping triangles. A closed surface of genus g has Euler
+/// characteristic V-E+F = 2-2g, so `expected_euler_characteristic` is 2 for the hull of a solid.
+pub fn validate(
+    triangles: &Triangles,
+    locations: &Locations,
+    expected_euler_characteristic: i64,
+) -> MeshReport {
+    let eps = 1e-9;
+
+    // Number of times each directed edge is used.
+    let mut directed = HashMap::<(VertexId, VertexId), usize>::new();
+    for tri in triangles {
+        for k in 0..3 {
+            *directed.entry((tri[k], tri[(k + 1) % 3])).or_default() += 1;
+        }
+    }
+    let mut undirected = HashMap::<(VertexId, VertexId), usize>::new();
+    for (&(a, b), &count) in directed.iter() {
+        *undirected.entry((a.min(b), a.max(b))).or_default() += count;
+    }
+
+    let boundary_edges = undirected.values().filter(|&&count| count == 1).count();
+    let non_manifold_edges = undirected.values().filter(|&&count| count > 2).count();
+    let inconsistent_edges = directed.values().filter(|&&count| count > 1).count();
+
+    // The triangles around a vertex must be connected through edges at that vertex.
+    let mut vertex_triangles = HashMap::<VertexId, Vec<usize>>::new();
+    for (t, tri) in triangles.iter().enumerate() {
+        for id in tri {
+            vertex_triangles.entry(*id).or_default().push(t);
+        }
+    }
+    let non_manifold_vertices = vertex_triangles
+        .iter()
+        .filter(|(vertex, fan)| {
+            let mut reached = vec![fan[0]];
+            let mut stack = vec![fan[0]];
+            while let Some(t) = stack.pop() {
+                for &other in fan.iter() {
+                    let shared = triangles[t]
+                        .iter()
+                        .filter(|id| *id != *vertex && triangles[other].contains(id))
+                        .count();
+                    if shared > 0 && !reached.contains(&other) {
+                        reached.push(other);
+                        stack.push(other);
+                    }
+                }
+            }
+            reached.len() != fan.len()
+        })
+        .count();
+
+    let scale = locations
+        .iter()
+        .map(|p| p.coords.norm())
+        .fold(0.0, f64::max)
+        .max(eps);
+    let normals: Vec<nalgebra::Vector3<f64>> = triangles
+        .iter()
+        .map(|tri| {
+            let [p0, p1, p2] = tri.map(|id| locations[id]);
+            (p1 - p0).cross(&(p2 - p0))
+        })
+        .collect();
+    let degenerate_triangles = normals
+        .iter()
+        .filter(|n| n.norm() < eps * scale * scale)
+        .count();
+
+    let mut overlapping_triangles = 0;
+    for a in 0..triangles.len() {
+        for b in (a + 1)..triangles.len() {
+            if triangles_overlap(
+                triangles[a],
+                triangles[b],
+                &normals[a],
+                locations,
+                eps * scale,
+            ) {
+                overlapping_triangles += 1;
+            }
+        }
+    }
+
+    let signed_volume = triangles
+        .iter()
+        .map(|tri| {
+            let [p0, p1, p2] = tri.map(|id| locations[id].coords);
+            p0.dot(&p1.cross(&p2)) / 6.0
+        })
+        .sum();
+
+    MeshReport {
+        vertices: vertex_triangles.len(),
+        edges: undirected.len(),
+        triangles: triangles.len(),
+        expected_euler_characteristic,
+        boundary_edges,
+        non_manifold_edges,
+        non_manifold_vertices,
+        inconsistent_edges,
+        degenerate_triangles,
+        overlapping_triangles,
+        signed_volume,
+    }
+}
+
+// Whether two coplanar triangles overlap in more than an edge or a point, by the separating axis
+// theorem in the plane of `a`.
+fn triangles_overlap(
+    a: [VertexId; 3],
+    b: [VertexId; 3],
+    normal: &nalgebra::Vector3<f64>,
+    locations: &Locations,
+    eps: f64,
+) -> bool {
+    if normal.norm() < eps {
+        return false;
+    }
+    let n = normal.normalize();
+    let p0 = locations[a[0]];
+    if b.iter().any(|id| n.dot(&(locations[*id] - p0)).abs() > eps) {
+        return false; // not coplanar
+    }
+
+    let u = (locations[a[1]] - p0).normalize();
+    let v = n.cross(&u);
+    let project = |tri: [VertexId; 3]| {
+        tri.map(|id| {
+            let w = locations[id] - p0;
+            nalgebra::Vector2::new(w.dot(&u), w.dot(&v))
+        })
+    };
+    let (a, b) = (project(a), project(b));
+
+    for tri in [&a, &b] {
+        for k in 0..3 {
+            let edge = tri[(k + 1) % 3] - tri[k];
+            let axis = nalgebra::Vector2::new(-edge.y, edge.x);
+            let interval = |tri: &[nalgebra::Vector2<f64>; 3]| {
+                let d = tri.map(|p| axis.dot(&p));
+                (d[0].min(d[1]).min(d[2]), d[0].max(d[1]).max(d[2]))
+            };
+            let ((a_min, a_max), (b_min, b_max)) = (interval(&a), interval(&b));
+            if a_max.min(b_max) - a_min.max(b_min) <= eps * axis.norm() {
+                return false; // separated, or only touching
+            }
+        }
+    }
+    true
+}
+
 pub fn to_stl<P: AsRef<Path>>(
     name: String,
     path: P,
@@ -118,3 +518,65 @@ pub fn to_stl<P: AsRef<Path>>(
     w.flush()?;
     Ok(())
 }
+
+pub fn to_obj<P: AsRef<Path>>(
+    name: String,
+    path: P,
+    triangles: &Vec<[VertexId; 3]>,
+    locations: &Locations,
+) -> std::io::Result<()> {
+    let file = File::create(path)?;
+    let mut w = BufWriter::new(file);
+
+    writeln!(w, "o {}", name)?;
+
+    for p in locations {
+        writeln!(w, "v {:.6} {:.6} {:.6}", p.x, p.y, p.z)?;
+    }
+
+    // Obj indices are 1-based.
+    for [i0, i1, i2] in triangles {
+        writeln!(w, "f {} {} {}", i0 + 1, i1 + 1, i2 + 1)?;
+    }
+
+    w.flush()?;
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use nalgebra::Point3;
+
+    use super::*;
+
+    #[test]
+    fn validate_reports_the_overlapping_triangles_of_an_exact_cube() {
+        // Every three corners of a square face are a hull facet, so each face comes out as four
+        // triangles covering it twice.
+        let locations: Locations = (0..8)
+            .map(|k| {
+                let coordinate = |bit: usize| if k >> bit & 1 == 1 { 1.0 } else { -1.0 };
+                Point3::new(coordinate(0), coordinate(1), coordinate(2))
+            })
+            .collect();
+        let report = validate(&hull_triangles(&locations), &locations, 2);
+        assert!(report.overlapping_triangles > 0);
+        assert!(report.non_manifold_edges > 0);
+        assert!(!report.is_valid());
+    }
+
+    #[test]
+    fn validate_passes_a_tetrahedron() {
+        let locations: Locations = vec![
+            Point3::new(1.0, 1.0, 1.0),
+            Point3::new(1.0, -1.0, -1.0),
+            Point3::new(-1.0, 1.0, -1.0),
+            Point3::new(-1.0, -1.0, 1.0),
+        ];
+        let report = validate(&hull_triangles(&locations), &locations, 2);
+        assert_eq!(report.triangles, 4);
+        assert_eq!(report.overlapping_triangles, 0);
+        assert_eq!(report.non_manifold_edges, 0);
+        assert!(report.is_valid());
+    }
+}
@@ -1,65 +1,360 @@
 //! Very simple rendering.
 //!
 //! Characteristics:
-//! - orthographic camera
-//! - image size (w,h) and pixel size derived from sensor size
-//! - color / shade of the object in view will be proportional to the angle of incidence of the
-//!   light ray on the object.
+//! - orthographic or perspective camera
+//! - image size (w,h) and pixel size derived from sensor size (orthographic) or field of view
+//!   (perspective)
+//! - color / shade of the object in view comes from the `light::Lighting` model: ambient, diffuse
+//!   and specular light from any number of lights, by default a single light at the camera.
+//! - the color of each face is set on the solid, see `color::Coloring`.
+//! - images are RGB, (h, w, 3) arrays.
+//! - the edges and vertices of the graph can be drawn on top, see `overlay`.
+//! - the ray tracer can cast several rays per pixel and average them, to smooth edges.
+//! - the camera can be moved and zoomed to fit the solid, see `ViewParams::frame`.
+//! - the ray tracer can also cast shadows, darken creases with ambient occlusion, and stand the
+//!   solid on a ground plane, see `scene`. The rasterizer does none of these.
+//! - the solid and graph can also be drawn as a vector image, see `svg`.
+
+pub mod color;
+pub mod light;
+pub mod overlay;
+mod raster;
+pub mod scene;
+pub mod svg;
+pub mod text;
+
+use clap::ValueEnum;
+use rand::Rng;
+use rayon::prelude::*;
+use strum::Display;
+
+use crate::solid::{Locations, Neighbors, Solid};
+
+// Space left around a framed solid, as a factor of its bounding sphere.
+const FRAMING_MARGIN: f64 = 1.1;
 
 pub trait Draw {
-    /// Gets the shade of the pixel given the ray.
-    /// Should return floats within [0,1].
+    /// Gets the color of the pixel given the ray.
+    /// Should return colors within [0,1].
     fn intersect(
         &self,
         ray_source: nalgebra::Point3<f64>,
         ray_direction: nalgebra::UnitVector3<f64>,
-    ) -> f64;
+        lighting: &light::Lighting,
+    ) -> color::Color;
+}
+
+#[derive(Clone, Copy, Debug, Display, PartialEq, ValueEnum)]
+#[strum(serialize_all = "kebab-case")]
+pub enum Projection {
+    /// All rays run along `camera_normal`, `pixel_size` apart.
+    Orthographic,
+    /// All rays start at `camera_center` (the eye) and fan out over `fov_deg`.
+    Perspective,
+}
+
+#[derive(Clone, Copy, Debug, Display, PartialEq, ValueEnum)]
+#[strum(serialize_all = "kebab-case")]
+pub enum Renderer {
+    /// Cast a ray through every pixel.
+    Raytrace,
+    /// Project every triangle and fill it in, keeping the nearest with a depth buffer.
+    Raster,
+    /// Draw the edges of the graph only, without the hull.
+    Wireframe,
+}
+
+#[derive(Clone, Copy, Debug, Display, PartialEq, ValueEnum)]
+#[strum(serialize_all = "kebab-case")]
+pub enum Framing {
+    /// Keep the camera where it was put.
+    None,
+    /// Fit every frame to the solid in it.
+    PerFrame,
+    /// Fit all frames of an animation to the solid in all of them, so the camera stays put.
+    WholeRun,
+}
+
+#[derive(Clone, Copy, Debug, Display, PartialEq, ValueEnum)]
+#[strum(serialize_all = "kebab-case")]
+pub enum Sampling {
+    /// Rays on a regular grid over the pixel.
+    Grid,
+    /// One ray at a random spot in each cell of the grid.
+    Jitter,
 }
 
+#[derive(Clone, Copy, Debug, Display, PartialEq, ValueEnum)]
+#[strum(serialize_all = "kebab-case")]
+pub enum Filter {
+    /// Average the rays within the pixel.
+    Box,
+    /// Weight rays up to a pixel away from the center, less the further they are.
+    Tent,
+}
+
+#[derive(Clone)]
 pub struct ViewParams {
-    /// Center of camera sensor
+    /// Center of camera sensor, or the eye for a perspective camera
     pub camera_center: nalgebra::Point3<f64>,
     /// Normal direction of the camera sensor
     pub camera_normal: nalgebra::UnitVector3<f64>,
+    /// Direction that is up in the image
+    pub camera_up: nalgebra::Vector3<f64>,
+    pub projection: Projection,
+    /// Vertical field of view in degrees, for the perspective camera
+    pub fov_deg: f64,
     /// Width of sensor in px
     pub image_width_px: usize,
     /// Height of sensor in px
     pub image_height_px: usize,
-    /// Pixel size
+    /// Pixel size, for the orthographic camera
     pub pixel_size: f64,
+    pub renderer: Renderer,
+    pub lighting: light::Lighting,
+    pub overlay: overlay::OverlayParams,
+    /// Make further edges fainter, for the wireframe renderer
+    pub depth_cue: bool,
+    /// Rays per pixel along each side, for the ray tracer. 1 casts a single ray through the
+    /// center.
+    pub samples: usize,
+    pub sampling: Sampling,
+    pub filter: Filter,
+    /// Stand the solid on a ground plane, for the ray tracer
+    pub ground: bool,
+    /// Cast shadows, for the ray tracer
+    pub shadows: bool,
+    /// Ambient occlusion, for the ray tracer
+    pub occlusion: scene::Occlusion,
+    /// How the callers of `frame` should fit the camera to the solid
+    pub framing: Framing,
+}
+
+impl ViewParams {
+    /// Put the camera at `eye`, looking at `target`, with `up` pointing up in the image.
+    pub fn look_at(
+        &mut self,
+        eye: nalgebra::Point3<f64>,
+        target: nalgebra::Point3<f64>,
+        up: nalgebra::Vector3<f64>,
+    ) {
+        self.camera_center = eye;
+        self.camera_normal = nalgebra::Unit::new_normalize(target - eye);
+        self.camera_up = up;
+    }
+
+    /// Move the camera along its normal, and zoom, so the sphere around `center` of radius
+    /// `radius` just fits in the image.
+    pub fn frame(&mut self, center: nalgebra::Point3<f64>, radius: f64) {
+        let radius = FRAMING_MARGIN * radius.max(1e-9);
+        let narrow_side = self.image_width_px.min(self.image_height_px) as f64;
+        let distance = match self.projection {
+            Projection::Orthographic => {
+                self.pixel_size = 2.0 * radius / narrow_side;
+                // Anywhere outside the sphere will do.
+                2.0 * radius
+            }
+            Projection::Perspective => {
+                let pixel_angle =
+                    2.0 * (0.5 * self.fov_deg.to_radians()).tan() / self.image_height_px as f64;
+                let half_angle = (0.5 * narrow_side * pixel_angle).atan();
+                radius / half_angle.sin()
+            }
+        };
+        self.camera_center = center - distance * *self.camera_normal;
+    }
+
+    /// The ray through the point (x, y) of the image, in pixels from its corner.
+    pub fn ray(&self, x: f64, y: f64) -> (nalgebra::Point3<f64>, nalgebra::UnitVector3<f64>) {
+        let (u, v) = basis(self.camera_normal, self.camera_up);
+
+        // Offset from the center of the image, in pixels.
+        let dx = x - 0.5 * self.image_width_px as f64;
+        let dy = y - 0.5 * self.image_height_px as f64;
+
+        match self.projection {
+            Projection::Orthographic => (
+                self.camera_center + self.pixel_size * dx * u + self.pixel_size * dy * v,
+                self.camera_normal,
+            ),
+            Projection::Perspective => {
+                // Size of a pixel on an image plane at distance 1 from the eye.
+                let pixel_angle =
+                    2.0 * (0.5 * self.fov_deg.to_radians()).tan() / self.image_height_px as f64;
+                let direction = *self.camera_normal + pixel_angle * dx * u + pixel_angle * dy * v;
+                (self.camera_center, nalgebra::Unit::new_normalize(direction))
+            }
+        }
+    }
+
+    /// Where `point` lands in the image, in pixels from its corner, and its depth along
+    /// `camera_normal`. The inverse of `ray`.
+    pub fn project(&self, point: nalgebra::Point3<f64>) -> (f64, f64, f64) {
+        let (u, v) = basis(self.camera_normal, self.camera_up);
+        let relative = point - self.camera_center;
+        let depth = relative.dot(&self.camera_normal);
+
+        let (dx, dy) = match self.projection {
+            Projection::Orthographic => (
+                relative.dot(&u) / self.pixel_size,
+                relative.dot(&v) / self.pixel_size,
+            ),
+            Projection::Perspective => {
+                let pixel_angle =
+                    2.0 * (0.5 * self.fov_deg.to_radians()).tan() / self.image_height_px as f64;
+                (
+                    relative.dot(&u) / (depth * pixel_angle),
+                    relative.dot(&v) / (depth * pixel_angle),
+                )
+            }
+        };
+        (
+            dx + 0.5 * self.image_width_px as f64,
+            dy + 0.5 * self.image_height_px as f64,
+            depth,
+        )
+    }
 }
 
-pub fn view<D: Draw>(object: &D, cfg: &ViewParams) -> ndarray::Array2<u8> {
-    let (u, v) = basis(cfg.camera_normal);
+/// Parses a vector written `x,y,z`.
+pub fn parse_vector(s: &str) -> Result<nalgebra::Vector3<f64>, String> {
+    let xyz = s
+        .split(',')
+        .map(|c| c.trim().parse::<f64>().map_err(|e| e.to_string()))
+        .collect::<Result<Vec<_>, _>>()?;
+    match xyz[..] {
+        [x, y, z] => Ok(nalgebra::Vector3::new(x, y, z)),
+        _ => Err(format!("expected x,y,z, got {}", s)),
+    }
+}
 
-    let w = cfg.image_width_px as f64 * cfg.pixel_size;
-    let h = cfg.image_height_px as f64 * cfg.pixel_size;
+/// Center and radius of a sphere around all `locations`.
+pub fn bounding_sphere<'a>(
+    locations: impl IntoIterator<Item = &'a Locations>,
+) -> (nalgebra::Point3<f64>, f64) {
+    let points: Vec<nalgebra::Point3<f64>> = locations.into_iter().flatten().copied().collect();
+    let center = nalgebra::Point3::from(
+        points
+            .iter()
+            .map(|p| p.coords)
+            .sum::<nalgebra::Vector3<f64>>()
+            / points.len() as f64,
+    );
+    let radius = points
+        .iter()
+        .map(|p| nalgebra::distance(p, &center))
+        .fold(0.0, f64::max);
+    (center, radius)
+}
 
-    let origin = cfg.camera_center - 0.5 * w * u - 0.5 * h * v;
+/// Render `solid` with the renderer chosen in `cfg`, and the graph over it if asked for. The
+/// wireframe renderer uses only the locations of `solid`, not its triangles.
+pub fn render(solid: &Solid, neighbors: &Neighbors, cfg: &ViewParams) -> ndarray::Array3<u8> {
+    let mut image = match cfg.renderer {
+        Renderer::Raytrace => view(&scene::Scene::new(solid, cfg), cfg),
+        Renderer::Raster => raster::raster(solid, cfg),
+        Renderer::Wireframe => return overlay::wireframe(&solid.locations, neighbors, cfg),
+    };
+    overlay::overlay(&mut image, solid, neighbors, cfg, &cfg.overlay);
+    image
+}
 
-    let mut image = ndarray::Array2::<u8>::zeros((cfg.image_height_px, cfg.image_width_px));
-    for x in 0..cfg.image_width_px {
-        for y in 0..cfg.image_height_px {
-            let ray_source = origin
-                + cfg.pixel_size * ((x as f64) + 0.5) * u
-                + cfg.pixel_size * ((y as f64) + 0.5) * v;
+pub fn view<D: Draw + Sync>(object: &D, cfg: &ViewParams) -> ndarray::Array3<u8> {
+    let rows: Vec<Vec<color::Color>> = (0..cfg.image_height_px)
+        .into_par_iter()
+        .map(|y| {
+            let mut rng = rand::rng();
+            (0..cfg.image_width_px)
+                .map(|x| pixel(object, cfg, x, y, &mut rng))
+                .collect()
+        })
+        .collect();
 
-            image[(y, x)] = (object.intersect(ray_source, cfg.camera_normal) * 255.0) as u8;
+    let mut image = ndarray::Array3::<u8>::zeros((cfg.image_height_px, cfg.image_width_px, 3));
+    for (y, row) in rows.iter().enumerate() {
+        for (x, color) in row.iter().enumerate() {
+            set_pixel(&mut image, x, y, color);
         }
     }
     image
 }
 
-// Assume the sensor is in the x,y plane, and z is the normal plane of the camera.
-// If we want a new normal, what is the basis?
-fn basis(normal: nalgebra::UnitVector3<f64>) -> (nalgebra::Vector3<f64>, nalgebra::Vector3<f64>) {
+// Color of the pixel (x, y), the weighted mean of the rays cast for it.
+fn pixel<D: Draw>(
+    object: &D,
+    cfg: &ViewParams,
+    x: usize,
+    y: usize,
+    rng: &mut impl Rng,
+) -> color::Color {
+    let n = cfg.samples.max(1);
+    if n == 1 {
+        let (ray_source, ray_direction) = cfg.ray(x as f64 + 0.5, y as f64 + 0.5);
+        return object.intersect(ray_source, ray_direction, &cfg.lighting);
+    }
+
+    // Half the width of the area sampled around the pixel center.
+    let radius = match cfg.filter {
+        Filter::Box => 0.5,
+        Filter::Tent => 1.0,
+    };
+    let mut total = color::Color::zeros();
+    let mut total_weight = 0.0;
+    for i in 0..n {
+        for j in 0..n {
+            // Where in cell (i, j) of the grid to cast the ray, from 0 to 1.
+            let (u, v) = match cfg.sampling {
+                Sampling::Grid => (0.5, 0.5),
+                Sampling::Jitter => (rng.random(), rng.random()),
+            };
+            let dx = radius * (2.0 * (i as f64 + u) / n as f64 - 1.0);
+            let dy = radius * (2.0 * (j as f64 + v) / n as f64 - 1.0);
+            let weight = match cfg.filter {
+                Filter::Box => 1.0,
+                Filter::Tent => (1.0 - dx.abs()) * (1.0 - dy.abs()),
+            };
+
+            let (ray_source, ray_direction) = cfg.ray(x as f64 + 0.5 + dx, y as f64 + 0.5 + dy);
+            total += weight * object.intersect(ray_source, ray_direction, &cfg.lighting);
+            total_weight += weight;
+        }
+    }
+    total / total_weight
+}
+
+fn set_pixel(image: &mut ndarray::Array3<u8>, x: usize, y: usize, color: &color::Color) {
+    for (c, value) in color::to_rgb(color).into_iter().enumerate() {
+        image[(y, x, c)] = value;
+    }
+}
+
+// Directions of increasing image x (right) and y (down) on the sensor, for a camera looking along
+// `normal` with `up` pointing up.
+fn basis(
+    normal: nalgebra::UnitVector3<f64>,
+    up: nalgebra::Vector3<f64>,
+) -> (nalgebra::Vector3<f64>, nalgebra::Vector3<f64>) {
     let n = normal.normalize();
+    let right = n.cross(&up);
+    // Looking straight up or down, any roll will do.
+    let right = if right.norm() < 1e-9 {
+        perpendicular_basis(&n).0
+    } else {
+        right.normalize()
+    };
+    let down = n.cross(&right);
+    (right, down)
+}
+
+/// Two unit vectors perpendicular to the unit vector `n` and to each other.
+pub fn perpendicular_basis(
+    n: &nalgebra::Vector3<f64>,
+) -> (nalgebra::Vector3<f64>, nalgebra::Vector3<f64>) {
     let a = if n.z.abs() < 0.9 {
         nalgebra::Vector3::z()
     } else {
         nalgebra::Vector3::x()
     };
-    let u = a.cross(&n).normalize();
-    let v = n.cross(&u);
-    (u, v)
+    let u = n.cross(&a).normalize();
+    (u, n.cross(&u))
 }
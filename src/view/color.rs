@@ -0,0 +1,106 @@
+//! Colors of the faces of a solid.
+//!
+//! Faces are the polygons of the graph, found with `merge_faces`, so the triangles a face is cut
+//! into all get the same color.
+
+use clap::ValueEnum;
+use strum::Display;
+
+use crate::solid::{Locations, Neighbors, Triangles};
+use crate::triangulate::{face_normal, merge_faces};
+
+/// Red, green and blue within [0,1].
+pub type Color = nalgebra::Vector3<f64>;
+
+#[derive(Clone, Copy, Debug, Display, PartialEq, ValueEnum)]
+#[strum(serialize_all = "kebab-case")]
+pub enum ColorScheme {
+    /// Every face white.
+    Uniform,
+    /// Faces with the same number of sides get the same palette color: triangles the first,
+    /// quads the second and so on.
+    Valence,
+    /// Every face a different hue.
+    FaceIndex,
+    /// Faces colored by the direction of their normal, x/y/z as red/green/blue.
+    Normal,
+    /// Faces take the palette colors in turn.
+    Palette,
+}
+
+#[derive(Clone, Debug)]
+pub struct Coloring {
+    pub scheme: ColorScheme,
+    pub palette: Vec<Color>,
+}
+
+impl Coloring {
+    /// The color of every triangle of the hull of `locations`.
+    pub fn triangle_colors(
+        &self,
+        triangles: &Triangles,
+        locations: &Locations,
+        neighbors: &Neighbors,
+    ) -> Vec<Color> {
+        if self.scheme == ColorScheme::Uniform {
+            return vec![Color::repeat(1.0); triangles.len()];
+        }
+        assert!(!self.palette.is_empty(), "the palette has no colors");
+
+        let faces = merge_faces(triangles, locations, neighbors);
+        let face_colors: Vec<Color> = faces
+            .iter()
+            .enumerate()
+            .map(|(face_id, face)| match self.scheme {
+                ColorScheme::Uniform => unreachable!(),
+                ColorScheme::Valence => self.palette[(face.len() - 3) % self.palette.len()],
+                ColorScheme::FaceIndex => {
+                    // Golden ratio steps around the color wheel keep neighboring ids apart.
+                    hue((face_id as f64 * 0.618_033_988_75).fract())
+                }
+                ColorScheme::Normal => face_normal(face, locations).map(|c| 0.5 * (c + 1.0)),
+                ColorScheme::Palette => self.palette[face_id % self.palette.len()],
+            })
+            .collect();
+
+        // Each triangle lies in the one face holding all three of its vertices.
+        triangles
+            .iter()
+            .map(|tri| {
+                let face_id = faces
+                    .iter()
+                    .position(|face| tri.iter().all(|id| face.contains(id)))
+                    .expect("triangle outside every face");
+                face_colors[face_id]
+            })
+            .collect()
+    }
+}
+
+/// Fully saturated color of hue `h` in [0,1).
+fn hue(h: f64) -> Color {
+    let channel = |offset: f64| {
+        let k = (6.0 * h + offset) % 6.0;
+        1.0 - (k.min(4.0 - k).clamp(0.0, 1.0))
+    };
+    Color::new(channel(5.0), channel(3.0), channel(1.0))
+}
+
+/// Parses a hex color, `#rrggbb` or `rrggbb`.
+pub fn parse_color(s: &str) -> Result<Color, String> {
+    let hex = s.trim().trim_start_matches('#');
+    if hex.len() != 6 {
+        return Err(format!("expected #rrggbb, got {}", s));
+    }
+    let channel = |k: usize| {
+        u8::from_str_radix(&hex[2 * k..2 * k + 2], 16)
+            .map(|c| c as f64 / 255.0)
+            .map_err(|e| e.to_string())
+    };
+    Ok(Color::new(channel(0)?, channel(1)?, channel(2)?))
+}
+
+/// A color as bytes for an image.
+pub fn to_rgb(color: &Color) -> [u8; 3] {
+    [color.x, color.y, color.z].map(|c| (c.clamp(0.0, 1.0) * 255.0) as u8)
+}
@@ -0,0 +1,150 @@
+//! Lambert + Phong lighting.
+//!
+//! The shade of a surface is the ambient term plus, for every light, a diffuse term proportional
+//! to the cosine between the normal and the direction to the light, and a specular term that
+//! peaks where the light is mirrored into the camera. A single headlight with no ambient or
+//! specular light shades by the cosine between the normal and the view ray.
+
+use std::str::FromStr;
+
+use nalgebra::{Point3, UnitVector3, Vector3};
+
+use super::color::Color;
+use super::parse_vector;
+
+#[derive(Clone, Debug)]
+pub enum Light {
+    /// A light at the camera, shining along every view ray.
+    Headlight { intensity: f64 },
+    /// A light infinitely far away, shining along `direction`.
+    Directional {
+        direction: UnitVector3<f64>,
+        intensity: f64,
+    },
+    /// A light at `position`, shining in all directions.
+    Point {
+        position: Point3<f64>,
+        intensity: f64,
+    },
+}
+
+/// Parses `headlight`, `directional:x,y,z` or `point:x,y,z`, optionally followed by
+/// `:intensity`.
+impl FromStr for Light {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let mut parts = s.split(':');
+        let kind = parts.next().unwrap_or_default();
+        let vector = |part: Option<&str>| -> Result<Vector3<f64>, String> {
+            parse_vector(part.ok_or_else(|| format!("{} light needs x,y,z", kind))?)
+        };
+
+        let light = match kind {
+            "headlight" => Light::Headlight { intensity: 1.0 },
+            "directional" => Light::Directional {
+                direction: UnitVector3::new_normalize(vector(parts.next())?),
+                intensity: 1.0,
+            },
+            "point" => Light::Point {
+                position: vector(parts.next())?.into(),
+                intensity: 1.0,
+            },
+            _ => return Err(format!("unknown light {}", kind)),
+        };
+
+        let intensity = match parts.next() {
+            Some(part) => part.parse::<f64>().map_err(|e| e.to_string())?,
+            None => return Ok(light),
+        };
+        Ok(match light {
+            Light::Headlight { .. } => Light::Headlight { intensity },
+            Light::Directional { direction, .. } => Light::Directional {
+                direction,
+                intensity,
+            },
+            Light::Point { position, .. } => Light::Point {
+                position,
+                intensity,
+            },
+        })
+    }
+}
+
+#[derive(Clone, Debug)]
+pub struct Lighting {
+    pub lights: Vec<Light>,
+    /// Light reaching every surface, lit or not.
+    pub ambient: f64,
+    /// Weight of the Lambert term.
+    pub diffuse: f64,
+    /// Weight of the Phong term.
+    pub specular: f64,
+    /// Phong exponent; larger gives smaller, sharper highlights.
+    pub shininess: f64,
+}
+
+impl Lighting {
+    /// Color of the surface of color `color` at `point` with unit `normal`, seen along
+    /// `view_direction`. Highlights take the color of the light, which is white.
+    pub fn shade(
+        &self,
+        point: Point3<f64>,
+        normal: &Vector3<f64>,
+        view_direction: &UnitVector3<f64>,
+        color: &Color,
+    ) -> Color {
+        self.shade_occluded(point, normal, view_direction, color, 1.0, |_, _| true)
+    }
+
+    /// Like `shade`, for a surface only `ambient_visible` of the ambient light reaches, and that
+    /// a light reaches only if `reaches(to_light, distance)`, for the unit vector towards the
+    /// light and the distance to it.
+    pub fn shade_occluded(
+        &self,
+        point: Point3<f64>,
+        normal: &Vector3<f64>,
+        view_direction: &UnitVector3<f64>,
+        color: &Color,
+        ambient_visible: f64,
+        reaches: impl Fn(&Vector3<f64>, f64) -> bool,
+    ) -> Color {
+        let mut shade = self.ambient * ambient_visible * color;
+        for light in &self.lights {
+            // Unit vector from the surface towards the light, and how far away it is.
+            let (to_light, distance, intensity) = match light {
+                Light::Headlight { intensity } => {
+                    (-view_direction.into_inner(), f64::MAX, *intensity)
+                }
+                Light::Directional {
+                    direction,
+                    intensity,
+                } => (-direction.into_inner(), f64::MAX, *intensity),
+                Light::Point {
+                    position,
+                    intensity,
+                } => (
+                    (position - point).normalize(),
+                    nalgebra::distance(position, &point),
+                    *intensity,
+                ),
+            };
+
+            let cos = normal.dot(&to_light);
+            if cos <= 0.0 {
+                continue; // lit from behind
+            }
+            // The headlight lights what the camera sees, so it casts no shadows.
+            if !matches!(light, Light::Headlight { .. }) && !reaches(&to_light, distance) {
+                continue;
+            }
+            let reflected = 2.0 * cos * normal - to_light;
+            let highlight = reflected.dot(&-view_direction.into_inner()).max(0.0);
+
+            shade += intensity
+                * (self.diffuse * cos * color
+                    + Color::repeat(self.specular * highlight.powf(self.shininess)));
+        }
+        shade.map(|c| c.clamp(0.0, 1.0))
+    }
+}
@@ -0,0 +1,246 @@
+//! Edges and vertices of the graph drawn over a rendered image.
+//!
+//! Edges are anti-aliased lines and vertices discs, both a fixed size in pixels. A point of an
+//! edge is hidden if the ray through it hits the solid in front of it.
+//!
+//! The same drawing without any solid is the wireframe renderer.
+
+use clap::ValueEnum;
+use strum::Display;
+
+use super::color::{Color, to_rgb};
+use super::{Projection, ViewParams};
+use crate::solid::{Locations, Neighbors, Solid};
+
+// How far in front of a point the solid must be to hide it, so edges on the surface stay visible.
+const OCCLUSION_EPS: f64 = 1e-4;
+// Brightness of dimmed hidden edges and vertices, and of the furthest depth cued edges.
+pub(super) const DIM: f64 = 0.3;
+
+#[derive(Clone, Copy, Debug, Display, PartialEq, ValueEnum)]
+#[strum(serialize_all = "kebab-case")]
+pub enum HiddenEdges {
+    /// Don't draw hidden edges and vertices.
+    Hide,
+    /// Draw hidden edges and vertices faintly.
+    Dim,
+    /// Draw hidden edges dashed and hidden vertices faintly.
+    Dash,
+    /// Draw everything, hidden or not.
+    Show,
+}
+
+#[derive(Clone, Debug)]
+pub struct OverlayParams {
+    pub edges: bool,
+    pub vertices: bool,
+    pub hidden: HiddenEdges,
+    /// Width of edges in px
+    pub edge_width: f64,
+    /// Radius of vertices in px
+    pub vertex_radius: f64,
+    /// Length of the dashes and gaps of dashed edges in px
+    pub dash_length: f64,
+    pub color: Color,
+}
+
+/// Draw the edges and vertices of the graph on `solid` over `image`, a render of `solid`.
+pub fn overlay(
+    image: &mut ndarray::Array3<u8>,
+    solid: &Solid,
+    neighbors: &Neighbors,
+    cfg: &ViewParams,
+    params: &OverlayParams,
+) {
+    draw(
+        image,
+        &solid.locations,
+        neighbors,
+        cfg,
+        params,
+        |x, y, depth, along| {
+            if params.hidden == HiddenEdges::Show || !hidden(solid, cfg, x, y, depth) {
+                return 1.0;
+            }
+            match (params.hidden, along) {
+                (HiddenEdges::Hide, _) => 0.0,
+                (HiddenEdges::Dash, Some(along))
+                    if (along / params.dash_length) as usize % 2 == 1 =>
+                {
+                    0.0
+                }
+                (HiddenEdges::Dash, Some(_)) => 1.0,
+                _ => DIM,
+            }
+        },
+    );
+}
+
+/// Draw the edges of the graph alone, and its vertices if asked for. Needs no hull, so it shows
+/// the graph as it is even while it is still tangled. With `cfg.depth_cue`, further edges are
+/// fainter.
+pub fn wireframe(
+    locations: &Locations,
+    neighbors: &Neighbors,
+    cfg: &ViewParams,
+) -> ndarray::Array3<u8> {
+    let mut image = ndarray::Array3::<u8>::zeros((cfg.image_height_px, cfg.image_width_px, 3));
+
+    let depths = locations.iter().map(|p| cfg.project(*p).2);
+    let near = depths.clone().fold(f64::MAX, f64::min);
+    let far = depths.fold(f64::MIN, f64::max);
+
+    let params = OverlayParams {
+        edges: true,
+        ..cfg.overlay.clone()
+    };
+    draw(
+        &mut image,
+        locations,
+        neighbors,
+        cfg,
+        &params,
+        |_, _, depth, _| {
+            if cfg.depth_cue && far > near {
+                1.0 - (1.0 - DIM) * (depth - near) / (far - near)
+            } else {
+                1.0
+            }
+        },
+    );
+    image
+}
+
+// Draw the edges and vertices at `locations` as asked for by `params`, with the opacity of the
+// point at (x, y) in the image and `depth` given by `opacity(x, y, depth, along)`, where `along`
+// is how many px from the start of its edge it is, if it is on one.
+fn draw(
+    image: &mut ndarray::Array3<u8>,
+    locations: &Locations,
+    neighbors: &Neighbors,
+    cfg: &ViewParams,
+    params: &OverlayParams,
+    opacity: impl Fn(f64, f64, f64, Option<f64>) -> f64,
+) {
+    let projected: Vec<(f64, f64, f64)> = locations.iter().map(|p| cfg.project(*p)).collect();
+
+    if params.edges {
+        for (a, vertex_neighbors) in neighbors.iter().enumerate() {
+            for &b in vertex_neighbors.iter().filter(|&&b| a < b) {
+                let (start, end) = (projected[a], projected[b]);
+                if start.2 <= 0.0 || end.2 <= 0.0 {
+                    continue; // (partly) behind the camera
+                }
+                line(image, start, end, params, |x, y, t, along| {
+                    let depth = match cfg.projection {
+                        Projection::Orthographic => start.2 + t * (end.2 - start.2),
+                        // The inverse of depth is affine in the image.
+                        Projection::Perspective => 1.0 / ((1.0 - t) / start.2 + t / end.2),
+                    };
+                    opacity(x, y, depth, Some(along))
+                });
+            }
+        }
+    }
+
+    if params.vertices {
+        for &(x, y, depth) in projected.iter().filter(|(_, _, depth)| *depth > 0.0) {
+            let alpha = opacity(x, y, depth, None);
+            if alpha > 0.0 {
+                disc(image, x, y, params, alpha);
+            }
+        }
+    }
+}
+
+// Draw the line from `start` to `end`, with the opacity given by `opacity(x, y, t, along)` for
+// the point (x, y) a fraction `t`, or `along` px, of the way along it.
+fn line(
+    image: &mut ndarray::Array3<u8>,
+    start: (f64, f64, f64),
+    end: (f64, f64, f64),
+    params: &OverlayParams,
+    mut opacity: impl FnMut(f64, f64, f64, f64) -> f64,
+) {
+    let (dx, dy) = (end.0 - start.0, end.1 - start.1);
+    let length_sq = dx * dx + dy * dy;
+    let reach = 0.5 * params.edge_width + 1.0;
+    let (cols, rows) = pixel_range(
+        image,
+        (start.0.min(end.0) - reach, start.1.min(end.1) - reach),
+        (start.0.max(end.0) + reach, start.1.max(end.1) + reach),
+    );
+
+    for y in rows {
+        for x in cols.clone() {
+            let (xc, yc) = (x as f64 + 0.5, y as f64 + 0.5);
+            // Nearest point of the line to the pixel center.
+            let t = if length_sq > 0.0 {
+                (((xc - start.0) * dx + (yc - start.1) * dy) / length_sq).clamp(0.0, 1.0)
+            } else {
+                0.0
+            };
+            let (nx, ny) = (start.0 + t * dx, start.1 + t * dy);
+            let distance = ((xc - nx).powi(2) + (yc - ny).powi(2)).sqrt();
+
+            // Fraction of the pixel covered, roughly.
+            let coverage = (0.5 * params.edge_width + 0.5 - distance).clamp(0.0, 1.0);
+            if coverage > 0.0 {
+                let alpha = coverage * opacity(nx, ny, t, t * length_sq.sqrt());
+                blend(image, x, y, &params.color, alpha);
+            }
+        }
+    }
+}
+
+// Draw a vertex at (x, y).
+fn disc(image: &mut ndarray::Array3<u8>, x: f64, y: f64, params: &OverlayParams, alpha: f64) {
+    let r = params.vertex_radius;
+    let (cols, rows) = pixel_range(
+        image,
+        (x - r - 1.0, y - r - 1.0),
+        (x + r + 1.0, y + r + 1.0),
+    );
+    for py in rows {
+        for px in cols.clone() {
+            let distance = ((px as f64 + 0.5 - x).powi(2) + (py as f64 + 0.5 - y).powi(2)).sqrt();
+            let coverage = (r + 0.5 - distance).clamp(0.0, 1.0);
+            if coverage > 0.0 {
+                blend(image, px, py, &params.color, alpha * coverage);
+            }
+        }
+    }
+}
+
+// Columns and rows of the pixels of `image` within the box from `min` to `max`.
+fn pixel_range(
+    image: &ndarray::Array3<u8>,
+    min: (f64, f64),
+    max: (f64, f64),
+) -> (std::ops::Range<usize>, std::ops::Range<usize>) {
+    let (h, w, _) = image.dim();
+    let clip = |v: f64, n: usize| v.clamp(0.0, n as f64) as usize;
+    (
+        clip(min.0.floor(), w)..clip(max.0.ceil(), w),
+        clip(min.1.floor(), h)..clip(max.1.ceil(), h),
+    )
+}
+
+fn blend(image: &mut ndarray::Array3<u8>, x: usize, y: usize, color: &Color, alpha: f64) {
+    for (c, value) in to_rgb(color).into_iter().enumerate() {
+        let pixel = &mut image[(y, x, c)];
+        *pixel = (*pixel as f64 * (1.0 - alpha) + value as f64 * alpha).round() as u8;
+    }
+}
+
+// Whether the solid is in front of the point at (x, y) in the image and `depth` along the camera
+// normal.
+pub(super) fn hidden(solid: &Solid, cfg: &ViewParams, x: f64, y: f64, depth: f64) -> bool {
+    let (ray_source, ray_direction) = cfg.ray(x, y);
+    let source_depth = (ray_source - cfg.camera_center).dot(&cfg.camera_normal);
+    // Distance along the ray to the point.
+    let distance = (depth - source_depth) / ray_direction.dot(&cfg.camera_normal);
+    solid
+        .closest_hit(ray_source, ray_direction)
+        .is_some_and(|hit| hit.distance < distance - OCCLUSION_EPS)
+}
@@ -0,0 +1,101 @@
+//! Scanline rasterizer.
+//!
+//! Projects every triangle onto the image and fills the pixels whose centers it covers, row by
+//! row, keeping the nearest triangle in each pixel with a depth buffer. Lights the point seen in
+//! each pixel like the ray tracer, so the two give the same images up to which pixels count as
+//! covered at the edges.
+
+use super::{Projection, ViewParams, set_pixel};
+use crate::solid::Solid;
+
+pub fn raster(solid: &Solid, cfg: &ViewParams) -> ndarray::Array3<u8> {
+    let (w, h) = (cfg.image_width_px, cfg.image_height_px);
+    let mut image = ndarray::Array3::<u8>::zeros((h, w, 3));
+    let mut depth_buffer = ndarray::Array2::<f64>::from_elem((h, w), f64::MAX);
+
+    let projected: Vec<(f64, f64, f64)> = solid.locations.iter().map(|p| cfg.project(*p)).collect();
+
+    for (tri, color) in solid.triangles.iter().zip(solid.colors.iter()) {
+        let [a, b, c] = tri.map(|id| projected[id]);
+        let [p0, p1, p2] = tri.map(|id| solid.locations[id]);
+        let triangle_normal = (p1 - p0).cross(&(p2 - p0)).normalize();
+
+        // Behind (or, for perspective, too close to) the camera.
+        if [a, b, c].iter().any(|(_, _, depth)| *depth <= 0.0) {
+            continue;
+        }
+
+        // Twice the signed area, for barycentric coordinates.
+        let area = (b.0 - a.0) * (c.1 - a.1) - (c.0 - a.0) * (b.1 - a.1);
+        if area.abs() < 1e-12 {
+            continue;
+        }
+
+        let min_y = a.1.min(b.1).min(c.1);
+        let max_y = a.1.max(b.1).max(c.1);
+        let first_row = ((min_y - 0.5).ceil().max(0.0)) as usize;
+        let last_row = ((max_y - 0.5).floor().min(h as f64 - 1.0)).max(-1.0) as isize;
+
+        for y in first_row as isize..=last_row {
+            let yc = y as f64 + 0.5;
+
+            // Where the row crosses the edges of the triangle.
+            let mut x_left = f64::MAX;
+            let mut x_right = f64::MIN;
+            for (s, e) in [(a, b), (b, c), (c, a)] {
+                if (s.1 <= yc && yc <= e.1) || (e.1 <= yc && yc <= s.1) {
+                    let (x0, x1) = if s.1 == e.1 {
+                        (s.0.min(e.0), s.0.max(e.0))
+                    } else {
+                        let x = s.0 + (yc - s.1) * (e.0 - s.0) / (e.1 - s.1);
+                        (x, x)
+                    };
+                    x_left = x_left.min(x0);
+                    x_right = x_right.max(x1);
+                }
+            }
+            if x_left > x_right {
+                continue;
+            }
+
+            let first_col = ((x_left - 0.5).ceil().max(0.0)) as usize;
+            let last_col = ((x_right - 0.5).floor().min(w as f64 - 1.0)).max(-1.0) as isize;
+            for x in first_col as isize..=last_col {
+                let xc = x as f64 + 0.5;
+
+                // Barycentric coordinates in the image.
+                let l0 = ((b.0 - xc) * (c.1 - yc) - (c.0 - xc) * (b.1 - yc)) / area;
+                let l1 = ((c.0 - xc) * (a.1 - yc) - (a.0 - xc) * (c.1 - yc)) / area;
+                let l2 = 1.0 - l0 - l1;
+
+                // Depth is affine in the image for an orthographic camera; for a perspective
+                // camera its inverse is.
+                let depth = match cfg.projection {
+                    Projection::Orthographic => l0 * a.2 + l1 * b.2 + l2 * c.2,
+                    Projection::Perspective => 1.0 / (l0 / a.2 + l1 / b.2 + l2 / c.2),
+                };
+
+                let (y, x) = (y as usize, x as usize);
+                if depth < depth_buffer[(y, x)] {
+                    depth_buffer[(y, x)] = depth;
+                    // Barycentric coordinates on the triangle itself, to light the point seen.
+                    let (w0, w1, w2) = match cfg.projection {
+                        Projection::Orthographic => (l0, l1, l2),
+                        Projection::Perspective => {
+                            (depth * l0 / a.2, depth * l1 / b.2, depth * l2 / c.2)
+                        }
+                    };
+                    let point =
+                        nalgebra::Point3::from(w0 * p0.coords + w1 * p1.coords + w2 * p2.coords);
+                    let (_, ray_direction) = cfg.ray(xc, yc);
+                    let shade = cfg
+                        .lighting
+                        .shade(point, &triangle_normal, &ray_direction, color);
+                    set_pixel(&mut image, x, y, &shade);
+                }
+            }
+        }
+    }
+
+    image
+}
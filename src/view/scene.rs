@@ -0,0 +1,143 @@
+//! The solid as the ray tracer sees it: standing on an optional ground plane, casting shadows and
+//! darkening the ambient light in its creases.
+//!
+//! Shadows are found by casting a ray from the surface towards each light, and ambient occlusion
+//! by casting rays in random directions around the normal and counting how many hit something
+//! nearby.
+
+use nalgebra::{Point3, UnitVector3, Vector3};
+use rand::Rng;
+
+use super::color::Color;
+use super::light::Lighting;
+use super::{Draw, ViewParams, perpendicular_basis};
+use crate::solid::Solid;
+
+// How far off the surface to start secondary rays, so they don't hit the surface they leave.
+const RAY_OFFSET: f64 = 1e-6;
+const GROUND_COLOR: f64 = 0.8;
+
+#[derive(Clone, Copy, Debug)]
+pub struct Occlusion {
+    /// Rays cast per visible point; 0 turns ambient occlusion off.
+    pub samples: usize,
+    /// Only what is within this distance of a point occludes it.
+    pub distance: f64,
+}
+
+pub struct Scene<'a> {
+    solid: &'a Solid,
+    /// Point on and upward normal of the ground plane.
+    ground: Option<(Point3<f64>, UnitVector3<f64>)>,
+    shadows: bool,
+    occlusion: Occlusion,
+}
+
+struct Hit {
+    distance: f64,
+    normal: Vector3<f64>,
+    color: Color,
+}
+
+impl<'a> Scene<'a> {
+    /// The scene for rendering `solid` as set up in `cfg`. The ground, if any, is perpendicular to
+    /// `cfg.camera_up` and touches the bottom of the solid.
+    pub fn new(solid: &'a Solid, cfg: &ViewParams) -> Self {
+        let ground = cfg.ground.then(|| {
+            let up = UnitVector3::new_normalize(cfg.camera_up);
+            let lowest = solid
+                .locations
+                .iter()
+                .min_by(|a, b| a.coords.dot(&up).total_cmp(&b.coords.dot(&up)))
+                .copied()
+                .unwrap_or_else(Point3::origin);
+            (lowest, up)
+        });
+        Scene {
+            solid,
+            ground,
+            shadows: cfg.shadows,
+            occlusion: cfg.occlusion,
+        }
+    }
+
+    fn closest_hit(&self, ray_source: Point3<f64>, ray_direction: UnitVector3<f64>) -> Option<Hit> {
+        let solid_hit = self
+            .solid
+            .closest_hit(ray_source, ray_direction)
+            .map(|hit| Hit {
+                distance: hit.distance,
+                normal: hit.normal,
+                color: self.solid.colors[hit.triangle],
+            });
+
+        // Only the top of the ground can be seen.
+        let ground_hit = self.ground.and_then(|(point, up)| {
+            let toward = ray_direction.dot(&up);
+            let distance = (point - ray_source).dot(&up) / toward;
+            (toward < 0.0 && distance > 0.0).then(|| Hit {
+                distance,
+                normal: up.into_inner(),
+                color: Color::repeat(GROUND_COLOR),
+            })
+        });
+
+        match (solid_hit, ground_hit) {
+            (Some(a), Some(b)) => Some(if a.distance <= b.distance { a } else { b }),
+            (a, b) => a.or(b),
+        }
+    }
+
+    // Fraction of the ambient light reaching `point`.
+    fn ambient_visible(&self, point: Point3<f64>, normal: &Vector3<f64>) -> f64 {
+        if self.occlusion.samples == 0 {
+            return 1.0;
+        }
+        let mut rng = rand::rng();
+        let (u, v) = perpendicular_basis(normal);
+        let open = (0..self.occlusion.samples)
+            .filter(|_| {
+                // Cosine weighted direction around the normal.
+                let (r1, r2): (f64, f64) = (rng.random(), rng.random());
+                let (r, phi) = (r1.sqrt(), 2.0 * std::f64::consts::PI * r2);
+                let direction = UnitVector3::new_normalize(
+                    r * phi.cos() * u + r * phi.sin() * v + (1.0 - r1).sqrt() * normal,
+                );
+                self.closest_hit(point, direction)
+                    .is_none_or(|hit| hit.distance > self.occlusion.distance)
+            })
+            .count();
+        open as f64 / self.occlusion.samples as f64
+    }
+}
+
+impl Draw for Scene<'_> {
+    fn intersect(
+        &self,
+        ray_source: Point3<f64>,
+        ray_direction: UnitVector3<f64>,
+        lighting: &Lighting,
+    ) -> Color {
+        let Some(hit) = self.closest_hit(ray_source, ray_direction) else {
+            return Color::zeros();
+        };
+        let point = ray_source + hit.distance * *ray_direction;
+        // Start secondary rays just off the surface.
+        let origin = point + RAY_OFFSET * hit.normal;
+
+        lighting.shade_occluded(
+            point,
+            &hit.normal,
+            &ray_direction,
+            &hit.color,
+            self.ambient_visible(origin, &hit.normal),
+            |to_light, distance| {
+                !self.shadows
+                    || self
+                        .solid
+                        .closest_hit(origin, UnitVector3::new_unchecked(*to_light))
+                        .is_none_or(|hit| hit.distance > distance)
+            },
+        )
+    }
+}
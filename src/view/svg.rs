@@ -0,0 +1,170 @@
+//! The solid as a vector image, for papers and slides.
+//!
+//! Every face is one flat shaded polygon, and they are drawn furthest first (the painter's
+//! algorithm), which is exact for a convex hull once the faces turned away from the camera are
+//! left out. The edges and vertices of the graph go on top, as set in `cfg.overlay`; an edge or
+//! vertex counts as hidden if the solid is in front of its middle.
+
+use std::fmt::Write;
+
+use super::color::{Color, to_rgb};
+use super::overlay::{DIM, HiddenEdges, hidden};
+use super::{Renderer, ViewParams};
+use crate::solid::{Neighbors, Solid};
+use crate::triangulate::{face_normal, merge_faces};
+
+/// An SVG document of `solid` as seen from the camera in `cfg`, with the graph in `neighbors`.
+pub fn svg(solid: &Solid, neighbors: &Neighbors, cfg: &ViewParams) -> String {
+    let (w, h) = (cfg.image_width_px, cfg.image_height_px);
+    let mut out = String::new();
+    writeln!(
+        out,
+        r#"<svg xmlns="http://www.w3.org/2000/svg" width="{w}" height="{h}" viewBox="0 0 {w} {h}">"#
+    )
+    .unwrap();
+    writeln!(out, r#"<rect width="{w}" height="{h}" fill="black"/>"#).unwrap();
+
+    let projected: Vec<(f64, f64, f64)> = solid.locations.iter().map(|p| cfg.project(*p)).collect();
+    let in_front = |ids: &[usize]| ids.iter().all(|id| projected[*id].2 > 0.0);
+
+    // Faces turned towards the camera, with their color and depth.
+    let mut faces: Vec<(Vec<usize>, Color, f64)> =
+        merge_faces(&solid.triangles, &solid.locations, neighbors)
+            .into_iter()
+            .filter(|face| in_front(face))
+            .filter_map(|face| {
+                let centroid = nalgebra::Point3::from(
+                    face.iter()
+                        .map(|id| solid.locations[*id].coords)
+                        .sum::<nalgebra::Vector3<f64>>()
+                        / face.len() as f64,
+                );
+                let normal = face_normal(&face, &solid.locations);
+                let (_, view_direction) = {
+                    let (x, y, _) = cfg.project(centroid);
+                    cfg.ray(x, y)
+                };
+                if normal.dot(&view_direction) >= 0.0 {
+                    return None;
+                }
+                // Every triangle of a face has its color.
+                let triangle = solid
+                    .triangles
+                    .iter()
+                    .position(|tri| tri.iter().all(|id| face.contains(id)))?;
+                let color =
+                    cfg.lighting
+                        .shade(centroid, &normal, &view_direction, &solid.colors[triangle]);
+                let depth = face.iter().map(|id| projected[*id].2).sum::<f64>() / face.len() as f64;
+                Some((face, color, depth))
+            })
+            .collect();
+    faces.sort_by(|a, b| b.2.total_cmp(&a.2));
+
+    for (face, color, _) in &faces {
+        let points: Vec<String> = face
+            .iter()
+            .map(|id| format!("{:.2},{:.2}", projected[*id].0, projected[*id].1))
+            .collect();
+        // Stroking in the fill color closes the hairline gaps between neighboring faces.
+        let color = hex(color);
+        write!(
+            out,
+            r#"<polygon points="{}" fill="{color}""#,
+            points.join(" ")
+        )
+        .unwrap();
+        writeln!(
+            out,
+            r#" stroke="{color}" stroke-width="0.5" stroke-linejoin="round"/>"#
+        )
+        .unwrap();
+    }
+
+    let params = &cfg.overlay;
+    let wireframe = cfg.renderer == Renderer::Wireframe;
+    let depths = projected.iter().map(|p| p.2);
+    let near = depths.clone().fold(f64::MAX, f64::min);
+    let far = depths.fold(f64::MIN, f64::max);
+    // Opacity of the point at `id` or the middle of the edge between two vertices, and whether
+    // to dash it, as `overlay::overlay` and `overlay::wireframe` do it.
+    let style = |ids: &[usize]| -> (f64, bool) {
+        let (x, y, depth) = ids.iter().fold((0.0, 0.0, 0.0), |acc, id| {
+            let p = projected[*id];
+            let n = ids.len() as f64;
+            (acc.0 + p.0 / n, acc.1 + p.1 / n, acc.2 + p.2 / n)
+        });
+        if wireframe {
+            let opacity = if cfg.depth_cue && far > near {
+                1.0 - (1.0 - DIM) * (depth - near) / (far - near)
+            } else {
+                1.0
+            };
+            return (opacity, false);
+        }
+        if params.hidden == HiddenEdges::Show || !hidden(solid, cfg, x, y, depth) {
+            return (1.0, false);
+        }
+        match params.hidden {
+            HiddenEdges::Hide => (0.0, false),
+            HiddenEdges::Dash if ids.len() == 2 => (1.0, true),
+            _ => (DIM, false),
+        }
+    };
+    let color = hex(&params.color);
+
+    if params.edges || wireframe {
+        for (a, vertex_neighbors) in neighbors.iter().enumerate() {
+            for &b in vertex_neighbors.iter().filter(|&&b| a < b) {
+                if !in_front(&[a, b]) {
+                    continue;
+                }
+                let (opacity, dashed) = style(&[a, b]);
+                if opacity <= 0.0 {
+                    continue;
+                }
+                let ((x1, y1, _), (x2, y2, _)) = (projected[a], projected[b]);
+                write!(
+                    out,
+                    r#"<line x1="{x1:.2}" y1="{y1:.2}" x2="{x2:.2}" y2="{y2:.2}" stroke="{color}""#
+                )
+                .unwrap();
+                write!(
+                    out,
+                    r#" stroke-width="{}" stroke-opacity="{opacity:.2}" stroke-linecap="round""#,
+                    params.edge_width
+                )
+                .unwrap();
+                if dashed {
+                    write!(out, r#" stroke-dasharray="{}""#, params.dash_length).unwrap();
+                }
+                writeln!(out, "/>").unwrap();
+            }
+        }
+    }
+
+    if params.vertices {
+        for (id, (x, y, _)) in projected.iter().enumerate() {
+            if !in_front(&[id]) {
+                continue;
+            }
+            let (opacity, _) = style(&[id]);
+            if opacity > 0.0 {
+                let (r, o) = (params.vertex_radius, opacity);
+                writeln!(
+                    out,
+                    r#"<circle cx="{x:.2}" cy="{y:.2}" r="{r}" fill="{color}" opacity="{o:.2}"/>"#
+                )
+                .unwrap();
+            }
+        }
+    }
+
+    out.push_str("</svg>\n");
+    out
+}
+
+fn hex(color: &Color) -> String {
+    let [r, g, b] = to_rgb(color);
+    format!("#{:02x}{:02x}{:02x}", r, g, b)
+}